@@ -12,6 +12,7 @@
 /// 2. **Investor Onboarding**: Agents pay entry fee + deposit capital → receive shares
 /// 3. **Trading Period**: Manager executes margin trades via DeepBook
 /// 4. **Settlement**: Fund closes, profits distributed to investors
+/// 5. **Teardown**: Once drained, the manager deletes the fund (`close_fund`)
 ///
 /// ## Key Security Properties
 ///
@@ -72,11 +73,26 @@ const EAuthorizationExpired: u64 = 18;
 const EAuthorizationPaused: u64 = 19;
 const EManagerAlreadyAuthorized: u64 = 20;
 const EArithmeticOverflow: u64 = 21;
+const EInvestorsNotRedeemed: u64 = 22;
+const ETraderCapRevoked: u64 = 23;
+const ERedemptionProcessed: u64 = 24;
+// 25 reserved (removed allowlist)
+const EZeroAddress: u64 = 26;
+const EFundNotEmpty: u64 = 27;
+const EWrongFund: u64 = 28;
+const EBelowMinTrade: u64 = 29;
+const ETermsLocked: u64 = 30;
 
 // ==================== Constants ====================
 // Maximum value that fits in u64 - used for overflow checks
 const U64_MAX: u128 = 18_446_744_073_709_551_615;
 const MIN_DEPOSIT: u64 = 100_000_000; // 0.1 SUI minimum
+/// Shares are fixed-point: at inception 1 MIST of deposit = `share_precision`
+/// shares. Later deposits get deposit * share_precision / share_price, so small
+/// deposits into a large fund still receive a non-zero share count. The default
+/// of 1_000 keeps total_shares within u64 for up to ~18M SUI of deposits.
+const DEFAULT_SHARE_PRECISION: u64 = 1_000;
+const MAX_SHARE_PRECISION: u64 = 1_000_000_000;
 const BASIS_POINTS: u64 = 10_000;
 const MS_PER_DAY: u64 = 86_400_000;
 
@@ -127,6 +143,48 @@ public struct HedgeFund has key {
     manager_fees: Balance<SUI>,
     /// Set of authorized manager addresses (prevents duplicate authorizations)
     authorized_managers: VecSet<address>,
+    /// Positions queued for payout after settlement, oldest first; cancelled
+    /// entries are removed so only live requests remain
+    redemption_queue: vector<Redemption>,
+    /// Index of the next queued redemption to pay out
+    redemption_cursor: u64,
+    /// Ticket handed to the next queued redemption
+    next_redemption_ticket: u64,
+    /// If true, manager fees are locked until every position is paid out
+    fees_after_investors: bool,
+    /// Positions issued and not yet paid out
+    outstanding_positions: u64,
+    /// Hurdle rate in basis points; performance fee only applies above it (0 = none)
+    hurdle_bps: u64,
+    /// Capital pool when trading started (base for the hurdle)
+    starting_capital: u64,
+    /// IDs of TraderCaps that may still execute trades
+    trader_caps: VecSet<ID>,
+    /// Share of each entry fee sent to the protocol treasury, in basis points;
+    /// the remainder pays the fund's APEX service (the provider)
+    entry_fee_protocol_bps: u64,
+    /// Entry fees routed to the protocol treasury
+    entry_fees_to_protocol: u64,
+    /// Entry fees credited to the APEX service provider (net of the APEX fee)
+    entry_fees_to_provider: u64,
+    /// IDs of every TradeRecord issued for this fund, oldest first
+    trade_ids: vector<ID>,
+    /// Smallest input_amount a trade may use (0 = no minimum)
+    min_trade_input: u64,
+    /// Shares issued per MIST at inception (see DEFAULT_SHARE_PRECISION)
+    share_precision: u64,
+    /// Capital level the fund must exceed before performance fees apply
+    /// (0 = none); combined with the hurdle, the higher bar wins
+    high_water_mark: u64,
+}
+
+/// A settled position waiting to be paid out by process_redemptions
+public struct Redemption has store, drop {
+    /// Stable identifier reported in RedemptionQueued; tickets only increase
+    ticket: u64,
+    investor: address,
+    shares: u64,
+    deposit_amount: u64,
 }
 
 /// Investor position in the fund
@@ -146,6 +204,14 @@ public struct InvestorPosition has key, store {
     withdrawal_pending: bool,
 }
 
+/// Delegated trade execution for a bot: can call execute_trader_trade but
+/// cannot settle the fund or withdraw fees. Not transferable.
+public struct TraderCap has key {
+    id: UID,
+    fund_id: ID,
+    trader: address,
+}
+
 /// Receipt for fund entry (proves entry fee was paid)
 public struct EntryReceipt has key, store {
     id: UID,
@@ -280,6 +346,31 @@ public struct InvestorWithdrew has copy, drop {
     amount_received: u64,
 }
 
+public struct RedemptionQueued has copy, drop {
+    fund_id: ID,
+    investor: address,
+    shares: u64,
+    ticket: u64,
+}
+
+public struct FundClosed has copy, drop {
+    fund_id: ID,
+    manager: address,
+}
+
+public struct RedemptionCancelled has copy, drop {
+    fund_id: ID,
+    investor: address,
+    shares: u64,
+    ticket: u64,
+}
+
+public struct RedemptionsProcessed has copy, drop {
+    fund_id: ID,
+    processed: u64,
+    remaining: u64,
+}
+
 public struct ManagerAuthorized has copy, drop {
     fund_id: ID,
     owner: address,
@@ -295,6 +386,17 @@ public struct ManagerRevoked has copy, drop {
     manager: address,
 }
 
+public struct TraderCapGranted has copy, drop {
+    fund_id: ID,
+    cap_id: ID,
+    trader: address,
+}
+
+public struct TraderCapRevoked has copy, drop {
+    fund_id: ID,
+    cap_id: ID,
+}
+
 public struct AuthorizedTradeExecuted has copy, drop {
     fund_id: ID,
     manager: address,
@@ -349,6 +451,21 @@ public fun create_fund(
         settled_at: 0,
         manager_fees: balance::zero(),
         authorized_managers: vec_set::empty(),
+        redemption_queue: vector[],
+        redemption_cursor: 0,
+        next_redemption_ticket: 0,
+        fees_after_investors: false,
+        outstanding_positions: 0,
+        hurdle_bps: 0,
+        starting_capital: 0,
+        trader_caps: vec_set::empty(),
+        entry_fee_protocol_bps: 0,
+        entry_fees_to_protocol: 0,
+        entry_fees_to_provider: 0,
+        trade_ids: vector[],
+        min_trade_input: 0,
+        share_precision: DEFAULT_SHARE_PRECISION,
+        high_water_mark: 0,
     };
 
     let fund_id = object::id(&fund);
@@ -365,15 +482,73 @@ public fun create_fund(
     fund_id
 }
 
+// ==================== Fund Terms ====================
+// Terms can only change while the fund is open and before the first deposit,
+// so no investor ever joins under one set of terms and exits under another.
+
+fun assert_terms_unlocked(fund: &HedgeFund, ctx: &TxContext) {
+    assert!(ctx.sender() == fund.manager, EUnauthorized);
+    assert!(fund.state == FUND_OPEN && fund.total_shares == 0, ETermsLocked);
+}
+
+/// Manager sets how many shares 1 MIST buys at inception
+///
+/// Larger values keep small deposits into a large fund precise at the cost of
+/// a lower capacity before total_shares overflows u64.
+public fun set_share_precision(fund: &mut HedgeFund, share_precision: u64, ctx: &TxContext) {
+    assert_terms_unlocked(fund, ctx);
+    assert!(share_precision > 0 && share_precision <= MAX_SHARE_PRECISION, EInvalidAmount);
+    fund.share_precision = share_precision;
+}
+
+/// Manager sets the hurdle rate: performance fees only apply to returns above it
+public fun set_hurdle_bps(fund: &mut HedgeFund, hurdle_bps: u64, ctx: &TxContext) {
+    assert_terms_unlocked(fund, ctx);
+    assert!(hurdle_bps <= BASIS_POINTS, EInvalidAmount); // Max 100%
+    fund.hurdle_bps = hurdle_bps;
+}
+
+/// Manager sets a high-water mark in MIST of fund capital
+///
+/// Carries a previous peak over (e.g. when relaunching a strategy), so no
+/// performance fee is charged until capital is back above it.
+public fun set_high_water_mark(fund: &mut HedgeFund, high_water_mark: u64, ctx: &TxContext) {
+    assert_terms_unlocked(fund, ctx);
+    fund.high_water_mark = high_water_mark;
+}
+
+/// Manager sets the share of each entry fee sent to the protocol treasury
+public fun set_entry_fee_protocol_bps(fund: &mut HedgeFund, entry_fee_protocol_bps: u64, ctx: &TxContext) {
+    assert_terms_unlocked(fund, ctx);
+    assert!(entry_fee_protocol_bps <= BASIS_POINTS, EInvalidAmount);
+    fund.entry_fee_protocol_bps = entry_fee_protocol_bps;
+}
+
+/// Manager sets the smallest input_amount a trade may use (0 = no minimum)
+public fun set_min_trade_input(fund: &mut HedgeFund, min_trade_input: u64, ctx: &TxContext) {
+    assert_terms_unlocked(fund, ctx);
+    fund.min_trade_input = min_trade_input;
+}
+
+/// Manager locks their fees until every investor position has been paid out
+public fun set_fees_after_investors(fund: &mut HedgeFund, fees_after_investors: bool, ctx: &TxContext) {
+    assert_terms_unlocked(fund, ctx);
+    fund.fees_after_investors = fees_after_investors;
+}
+
 // ==================== Investor Onboarding ====================
 
 /// Pay entry fee to join fund (returns receipt)
 /// This uses APEX payment system
+///
+/// `entry_fee_protocol_bps` of the fund's entry fee goes straight to the
+/// protocol treasury; the rest of the payment buys the APEX access, so it
+/// must still cover the service price.
 public fun pay_entry_fee(
-    fund: &HedgeFund,
+    fund: &mut HedgeFund,
     config: &mut ProtocolConfig,
     service: &mut ServiceProvider,
-    payment: Coin<SUI>,
+    mut payment: Coin<SUI>,
     clock: &Clock,
     ctx: &mut TxContext
 ): EntryReceipt {
@@ -383,7 +558,17 @@ public fun pay_entry_fee(
     let fee_amount = coin::value(&payment);
     assert!(fee_amount >= fund.entry_fee, EEntryFeeRequired);
 
+    // Protocol share of the entry fee (overpayment is not split)
+    let protocol_cut = (
+        ((fund.entry_fee as u128) * (fund.entry_fee_protocol_bps as u128)) / (BASIS_POINTS as u128)
+    ) as u64;
+    if (protocol_cut > 0) {
+        apex_payments::deposit_protocol_fee(config, coin::split(&mut payment, protocol_cut, ctx));
+    };
+    fund.entry_fees_to_protocol = fund.entry_fees_to_protocol + protocol_cut;
+
     // Create access capability (proves payment)
+    let revenue_before = apex_payments::service_revenue(service);
     let _capability = apex_payments::purchase_access(
         config,
         service,
@@ -395,6 +580,11 @@ public fun pay_entry_fee(
         ctx
     );
 
+    // Book what the service was actually credited: its price net of the
+    // APEX fee, not the nominal remainder (any excess is refunded)
+    fund.entry_fees_to_provider =
+        fund.entry_fees_to_provider + (apex_payments::service_revenue(service) - revenue_before);
+
     // Transfer capability to fund (or burn it - entry is one-time)
     transfer::public_transfer(_capability, fund.manager);
 
@@ -425,6 +615,16 @@ public fun deposit_capital(
     assert!(investor == ctx.sender(), EUnauthorized);
     object::delete(id);
 
+    issue_position(fund, deposit, clock::timestamp_ms(clock), ctx)
+}
+
+/// Add a deposit to the pool and mint the matching position
+fun issue_position(
+    fund: &mut HedgeFund,
+    deposit: Coin<SUI>,
+    entered_at: u64,
+    ctx: &mut TxContext
+): InvestorPosition {
     let deposit_amount = coin::value(&deposit);
     assert!(deposit_amount >= MIN_DEPOSIT, EInsufficientDeposit);
 
@@ -432,30 +632,32 @@ public fun deposit_capital(
     let current_capital = balance::value(&fund.capital_pool);
     assert!(current_capital + deposit_amount <= fund.max_capacity, EFundFull);
 
-    // Calculate shares (1:1 for first investor, proportional after)
+    // Calculate shares (share_precision per MIST for first investor, proportional after)
     // Use u128 intermediate calculation to prevent overflow with large amounts
-    let shares = if (fund.total_shares == 0) {
-        deposit_amount
+    let shares_u128 = if (fund.total_shares == 0) {
+        (deposit_amount as u128) * (fund.share_precision as u128)
     } else {
         // Safety: current_capital > 0 is guaranteed since fund.total_shares > 0
         // means at least one deposit has been made
         assert!(current_capital > 0, EInvalidAmount);
 
-        // Cast to u128 to handle multiplication of large MIST values without overflow
-        // e.g., 100 SUI * 100 SUI = 10^20 which exceeds u64::MAX
-        let shares_u128 = ((deposit_amount as u128) * (fund.total_shares as u128)) / (current_capital as u128);
-
-        // Verify result fits in u64 before casting
-        assert!(shares_u128 <= U64_MAX, EArithmeticOverflow);
-        (shares_u128 as u64)
+        // deposit * share_precision / share_price, where
+        // share_price = current_capital * share_precision / total_shares.
+        // Computed in one step so the share price is never rounded.
+        ((deposit_amount as u128) * (fund.total_shares as u128)) / (current_capital as u128)
     };
 
+    // Verify result (and the new total) fits in u64 before casting
+    assert!(shares_u128 + (fund.total_shares as u128) <= U64_MAX, EArithmeticOverflow);
+    let shares = (shares_u128 as u64);
+
     // Ensure shares > 0 to prevent dust deposits that dilute existing investors
     assert!(shares > 0, EInvalidAmount);
 
     // Add to capital pool
     balance::join(&mut fund.capital_pool, coin::into_balance(deposit));
     fund.total_shares = fund.total_shares + shares;
+    fund.outstanding_positions = fund.outstanding_positions + 1;
 
     let position = InvestorPosition {
         id: object::new(ctx),
@@ -463,7 +665,7 @@ public fun deposit_capital(
         investor: ctx.sender(),
         shares,
         deposit_amount,
-        entered_at: clock::timestamp_ms(clock),
+        entered_at,
         withdrawal_pending: false,
     };
 
@@ -496,6 +698,24 @@ public fun join_fund(
     deposit_capital(fund, receipt, deposit, clock, ctx)
 }
 
+/// join_fund, with the InvestorPosition shared instead of returned
+///
+/// For clients that can't round-trip owned objects between transactions: the
+/// position stays fetchable by ID, and withdrawal still requires the sender to
+/// be the recorded investor.
+public fun join_fund_shared(
+    fund: &mut HedgeFund,
+    config: &mut ProtocolConfig,
+    service: &mut ServiceProvider,
+    entry_fee_payment: Coin<SUI>,
+    deposit: Coin<SUI>,
+    clock: &Clock,
+    ctx: &mut TxContext
+) {
+    let position = join_fund(fund, config, service, entry_fee_payment, deposit, clock, ctx);
+    transfer::share_object(position);
+}
+
 // ==================== Trading Phase ====================
 
 /// Manager starts trading period (closes new investments)
@@ -510,6 +730,7 @@ public fun start_trading(
 
     fund.state = FUND_TRADING;
     fund.trading_started_at = clock::timestamp_ms(clock);
+    fund.starting_capital = balance::value(&fund.capital_pool);
 
     event::emit(TradingStarted {
         fund_id: object::id(fund),
@@ -529,7 +750,35 @@ public fun execute_margin_trade(
     ctx: &mut TxContext
 ): TradeRecord {
     assert!(ctx.sender() == fund.manager, EUnauthorized);
+    record_margin_trade(fund, trade_type, input_amount, simulated_output, clock, ctx)
+}
+
+/// Execute a margin trade as a delegated trader (same rules as execute_margin_trade)
+public fun execute_trader_trade(
+    cap: &TraderCap,
+    fund: &mut HedgeFund,
+    trade_type: vector<u8>,
+    input_amount: u64,
+    simulated_output: u64,
+    clock: &Clock,
+    ctx: &mut TxContext
+): TradeRecord {
+    assert!(cap.fund_id == object::id(fund), EUnauthorized);
+    assert!(vec_set::contains(&fund.trader_caps, object::uid_as_inner(&cap.id)), ETraderCapRevoked);
+    record_margin_trade(fund, trade_type, input_amount, simulated_output, clock, ctx)
+}
+
+/// Shared trade path once the caller has been authorized
+fun record_margin_trade(
+    fund: &mut HedgeFund,
+    trade_type: vector<u8>,
+    input_amount: u64,
+    simulated_output: u64,
+    clock: &Clock,
+    ctx: &mut TxContext
+): TradeRecord {
     assert!(fund.state == FUND_TRADING, EFundNotTrading);
+    assert!(input_amount >= fund.min_trade_input, EBelowMinTrade);
     assert!(balance::value(&fund.capital_pool) >= input_amount, EInsufficientDeposit);
 
     // Calculate P&L
@@ -586,6 +835,7 @@ public fun execute_margin_trade(
         is_profit,
         timestamp: clock::timestamp_ms(clock),
     };
+    vector::push_back(&mut fund.trade_ids, object::id(&record));
 
     event::emit(TradeExecuted {
         fund_id: object::id(fund),
@@ -698,6 +948,74 @@ public fun record_trade_profit(
     balance::join(&mut fund.capital_pool, coin::into_balance(profit));
 }
 
+// ==================== Trader Delegation ====================
+
+/// Manager delegates trade execution to `trader` without granting management
+public fun grant_trader_cap(
+    fund: &mut HedgeFund,
+    trader: address,
+    ctx: &mut TxContext
+): ID {
+    assert!(ctx.sender() == fund.manager, EUnauthorized);
+    // A cap sent to 0x0 could never be used or returned
+    assert!(trader != @0x0, EZeroAddress);
+
+    let cap = TraderCap {
+        id: object::new(ctx),
+        fund_id: object::id(fund),
+        trader,
+    };
+    let cap_id = object::id(&cap);
+    vec_set::insert(&mut fund.trader_caps, cap_id);
+
+    event::emit(TraderCapGranted {
+        fund_id: object::id(fund),
+        cap_id,
+        trader,
+    });
+
+    transfer::transfer(cap, trader);
+    cap_id
+}
+
+/// Manager revokes a TraderCap; the trader keeps the object but can no longer trade
+public fun revoke_trader_cap(
+    fund: &mut HedgeFund,
+    cap_id: ID,
+    ctx: &TxContext
+) {
+    assert!(ctx.sender() == fund.manager, EUnauthorized);
+    assert!(vec_set::contains(&fund.trader_caps, &cap_id), ETraderCapRevoked);
+    vec_set::remove(&mut fund.trader_caps, &cap_id);
+
+    event::emit(TraderCapRevoked {
+        fund_id: object::id(fund),
+        cap_id,
+    });
+}
+
+/// Trader discards a (possibly revoked) TraderCap
+public fun destroy_trader_cap(fund: &mut HedgeFund, cap: TraderCap) {
+    let TraderCap { id, fund_id, trader: _ } = cap;
+    if (fund_id == object::id(fund)) {
+        let cap_id = object::uid_to_inner(&id);
+        if (vec_set::contains(&fund.trader_caps, &cap_id)) {
+            vec_set::remove(&mut fund.trader_caps, &cap_id);
+        };
+    };
+    object::delete(id);
+}
+
+/// Trader discards a TraderCap without the fund, e.g. once the fund has been
+/// closed and can no longer be passed to destroy_trader_cap
+///
+/// If the fund still exists the cap's ID stays in its trader set; the manager
+/// can clear it with revoke_trader_cap.
+public fun burn_trader_cap(cap: TraderCap) {
+    let TraderCap { id, fund_id: _, trader: _ } = cap;
+    object::delete(id);
+}
+
 // ==================== Manager Authorization ====================
 
 /// Authorize a manager to trade with constraints (0 = unlimited for any limit)
@@ -825,6 +1143,7 @@ public fun execute_authorized_trade(
     auth.total_trades = auth.total_trades + 1;
 
     // === Execute Trade (same logic as execute_margin_trade) ===
+    assert!(input_amount >= fund.min_trade_input, EBelowMinTrade);
     assert!(pool_size >= input_amount, EInsufficientDeposit);
 
     // Calculate P&L
@@ -875,6 +1194,7 @@ public fun execute_authorized_trade(
         is_profit,
         timestamp: now,
     };
+    vector::push_back(&mut fund.trade_ids, object::id(&record));
 
     event::emit(AuthorizedTradeExecuted {
         fund_id: object::id(fund),
@@ -1048,7 +1368,26 @@ public fun settle_fund(
     assert!(ctx.sender() == fund.manager, EUnauthorized);
     assert!(fund.state == FUND_TRADING, EFundNotTrading);
 
-    // Calculate and deduct management fee
+    let total_fees = settlement_fees(fund);
+    if (total_fees > 0) {
+        let fee_balance = balance::split(&mut fund.capital_pool, total_fees);
+        balance::join(&mut fund.manager_fees, fee_balance);
+    };
+
+    fund.state = FUND_SETTLED;
+    fund.settled_at = clock::timestamp_ms(clock);
+
+    event::emit(FundSettled {
+        fund_id: object::id(fund),
+        final_capital: balance::value(&fund.capital_pool),
+        total_pnl: fund.realized_pnl,
+        is_profit: fund.is_profit,
+        manager_fees_collected: total_fees,
+    });
+}
+
+/// Fees settle_fund would deduct right now (zero if capital can't cover them)
+fun settlement_fees(fund: &HedgeFund): u64 {
     // Use u128 intermediate calculation to prevent overflow with large capital amounts
     let total_capital = balance::value(&fund.capital_pool);
 
@@ -1059,34 +1398,83 @@ public fun settle_fund(
     assert!(mgmt_fee_u128 <= U64_MAX, EArithmeticOverflow);
     let management_fee = (mgmt_fee_u128 as u64);
 
-    // Calculate performance fee (only on profits)
-    // Use u128 intermediate calculation to prevent overflow
-    let performance_fee = if (fund.is_profit && fund.realized_pnl > 0) {
-        let perf_fee_u128 = ((fund.realized_pnl as u128) * (fund.performance_fee_bps as u128)) / (BASIS_POINTS as u128);
-        assert!(perf_fee_u128 <= U64_MAX, EArithmeticOverflow);
-        (perf_fee_u128 as u64)
+    let total_fees = management_fee + performance_fee_due(fund);
+
+    // Fees are only deducted if there's enough capital
+    if (total_capital >= total_fees) { total_fees } else { 0 }
+}
+
+/// Payout for `shares` out of `total_capital`
+///
+/// Saturates: the result never exceeds `total_capital`, and a fund whose
+/// losses wiped out its capital pays zero rather than aborting.
+fun payout_for_shares(fund: &HedgeFund, total_capital: u64, shares: u64): u64 {
+    // Safety check: ensure total_shares > 0 to prevent division by zero
+    assert!(fund.total_shares > 0, EInvalidAmount);
+
+    if (total_capital == 0) {
+        return 0
+    };
+
+    // withdrawal_amount = (total_capital * shares) / total_shares
+    // Shares carry share_precision scaling, which cancels out in the ratio
+    // Use u128 intermediate calculation to handle large MIST values
+    if (shares >= fund.total_shares) {
+        // Last investor gets remaining balance (handles rounding)
+        return total_capital
+    };
+
+    // Strictly below total_capital since shares < total_shares, so the cast is safe
+    let amount_u128 = ((total_capital as u128) * (shares as u128)) / (fund.total_shares as u128);
+    (amount_u128 as u64)
+}
+
+/// Quote what withdraw_shares would pay for `position` without committing.
+/// Before settlement the pending management/performance fees are deducted
+/// first, so the quote matches the payout if the fund settled now.
+public fun preview_withdrawal(fund: &HedgeFund, position: &InvestorPosition): u64 {
+    assert!(position.fund_id == object::id(fund), EWrongFund);
+
+    let total_capital = balance::value(&fund.capital_pool);
+    let net_capital = if (fund.state == FUND_SETTLED) {
+        total_capital
     } else {
-        0
+        total_capital - settlement_fees(fund)
     };
 
-    let total_fees = management_fee + performance_fee;
+    payout_for_shares(fund, net_capital, position.shares)
+}
 
-    // Deduct fees if there's enough capital
-    if (total_fees > 0 && total_capital >= total_fees) {
-        let fee_balance = balance::split(&mut fund.capital_pool, total_fees);
-        balance::join(&mut fund.manager_fees, fee_balance);
+/// Performance fee owed on realized profit above the benchmark: the larger of
+/// the hurdle (starting_capital * hurdle_bps / 10000) and the high-water mark's
+/// excess over starting_capital; zero on a loss or below the benchmark
+public fun performance_fee_due(fund: &HedgeFund): u64 {
+    if (!fund.is_profit || fund.realized_pnl == 0) {
+        return 0
     };
 
-    fund.state = FUND_SETTLED;
-    fund.settled_at = clock::timestamp_ms(clock);
+    // Use u128 intermediate calculation to prevent overflow
+    let hurdle = ((fund.starting_capital as u128) * (fund.hurdle_bps as u128)) / (BASIS_POINTS as u128);
+
+    // Hurdle and high-water mark don't stack: profit is measured against
+    // whichever bar is higher
+    let starting_capital = fund.starting_capital as u128;
+    let high_water_mark = fund.high_water_mark as u128;
+    let above_high_water = if (high_water_mark > starting_capital) {
+        high_water_mark - starting_capital
+    } else {
+        0
+    };
+    let benchmark = if (above_high_water > hurdle) { above_high_water } else { hurdle };
 
-    event::emit(FundSettled {
-        fund_id: object::id(fund),
-        final_capital: balance::value(&fund.capital_pool),
-        total_pnl: fund.realized_pnl,
-        is_profit: fund.is_profit,
-        manager_fees_collected: total_fees,
-    });
+    let pnl = fund.realized_pnl as u128;
+    if (pnl <= benchmark) {
+        return 0
+    };
+
+    let perf_fee_u128 = ((pnl - benchmark) * (fund.performance_fee_bps as u128)) / (BASIS_POINTS as u128);
+    assert!(perf_fee_u128 <= U64_MAX, EArithmeticOverflow);
+    (perf_fee_u128 as u64)
 }
 
 /// Investor withdraws their share after settlement
@@ -1096,6 +1484,19 @@ public fun withdraw_shares(
     clock: &Clock,
     ctx: &mut TxContext
 ): SettlementReceipt {
+    let (receipt, withdrawal) = withdraw_shares_coin(fund, position, clock, ctx);
+    transfer::public_transfer(withdrawal, receipt.investor);
+    receipt
+}
+
+/// Same as withdraw_shares but returns the payout coin to the caller, so a
+/// PTB can split or route it in the same transaction
+public fun withdraw_shares_coin(
+    fund: &mut HedgeFund,
+    position: InvestorPosition,
+    clock: &Clock,
+    ctx: &mut TxContext
+): (SettlementReceipt, Coin<SUI>) {
     assert!(fund.state == FUND_SETTLED, EFundNotSettled);
 
     let InvestorPosition {
@@ -1108,35 +1509,17 @@ public fun withdraw_shares(
         withdrawal_pending: _,
     } = position;
 
-    assert!(fund_id == object::id(fund), EUnauthorized);
+    // A position only ever redeems against the fund that issued it
+    assert!(fund_id == object::id(fund), EWrongFund);
     assert!(investor == ctx.sender(), EUnauthorized);
     assert!(shares > 0, EInsufficientShares);
 
     object::delete(id);
 
     // Calculate share value
+    assert!(fund.total_shares >= shares, EInsufficientShares);
     let total_capital = balance::value(&fund.capital_pool);
-
-    // Safety check: ensure total_shares > 0 to prevent division by zero
-    assert!(fund.total_shares > 0, EInvalidAmount);
-
-    // Safe withdrawal calculation to avoid overflow
-    // withdrawal_amount = (total_capital * shares) / total_shares
-    // Use u128 intermediate calculation to handle large MIST values
-    let withdrawal_amount = if (shares == fund.total_shares) {
-        // Last investor gets remaining balance (handles rounding)
-        total_capital
-    } else {
-        // Cast to u128 to prevent overflow with large amounts
-        let amount_u128 = ((total_capital as u128) * (shares as u128)) / (fund.total_shares as u128);
-
-        // Verify result fits in u64 before casting
-        assert!(amount_u128 <= U64_MAX, EArithmeticOverflow);
-        (amount_u128 as u64)
-    };
-
-    // Ensure withdrawal amount doesn't exceed available capital (sanity check)
-    assert!(withdrawal_amount <= total_capital, EArithmeticOverflow);
+    let withdrawal_amount = payout_for_shares(fund, total_capital, shares);
 
     // Calculate profit share for receipt
     let profit_share = if (withdrawal_amount > deposit_amount) {
@@ -1145,16 +1528,13 @@ public fun withdraw_shares(
         0
     };
 
-    // Update fund shares (underflow protected by prior assertion that shares <= total_shares implicitly)
-    assert!(fund.total_shares >= shares, EArithmeticOverflow);
     fund.total_shares = fund.total_shares - shares;
+    fund.outstanding_positions = fund.outstanding_positions - 1;
 
-    // Transfer funds to investor
     let withdrawal = coin::from_balance(
         balance::split(&mut fund.capital_pool, withdrawal_amount),
         ctx
     );
-    transfer::public_transfer(withdrawal, investor);
 
     event::emit(InvestorWithdrew {
         fund_id: object::id(fund),
@@ -1163,7 +1543,7 @@ public fun withdraw_shares(
         amount_received: withdrawal_amount,
     });
 
-    SettlementReceipt {
+    let receipt = SettlementReceipt {
         id: object::new(ctx),
         fund_id,
         investor,
@@ -1171,16 +1551,181 @@ public fun withdraw_shares(
         amount_received: withdrawal_amount,
         profit_share,
         timestamp: clock::timestamp_ms(clock),
+    };
+
+    (receipt, withdrawal)
+}
+
+// ==================== Redemption Queue ====================
+
+/// Queue a position for payout instead of withdrawing it directly
+///
+/// Paying out hundreds of positions in one PTB can exceed gas limits, so
+/// investors (or a front-end on their behalf) queue positions here and anyone
+/// can crank process_redemptions in bounded batches. Positions can be queued
+/// while the fund is still trading; payouts only start once it settles.
+public fun request_redemption(
+    fund: &mut HedgeFund,
+    position: InvestorPosition,
+    ctx: &TxContext
+) {
+    assert!(fund.state == FUND_TRADING || fund.state == FUND_SETTLED, EFundNotSettled);
+
+    let InvestorPosition {
+        id,
+        fund_id,
+        investor,
+        shares,
+        deposit_amount,
+        entered_at: _,
+        withdrawal_pending: _,
+    } = position;
+
+    // A position only ever redeems against the fund that issued it
+    assert!(fund_id == object::id(fund), EWrongFund);
+    assert!(investor == ctx.sender(), EUnauthorized);
+    assert!(shares > 0, EInsufficientShares);
+
+    object::delete(id);
+
+    let ticket = fund.next_redemption_ticket;
+    fund.next_redemption_ticket = ticket + 1;
+    fund.redemption_queue.push_back(Redemption { ticket, investor, shares, deposit_amount });
+
+    event::emit(RedemptionQueued {
+        fund_id: object::id(fund),
+        investor,
+        shares,
+        ticket,
+    });
+}
+
+/// Take back a queued redemption that has not been paid out yet
+///
+/// `ticket` is the value reported in RedemptionQueued. The entry is removed
+/// from the queue (so it only ever holds live requests) and a fresh
+/// InvestorPosition with the same shares and deposit is returned, so the
+/// investor can withdraw_shares directly or queue again later. Aborts if the
+/// entry was already processed or cancelled.
+public fun cancel_redemption(
+    fund: &mut HedgeFund,
+    ticket: u64,
+    clock: &Clock,
+    ctx: &mut TxContext
+): InvestorPosition {
+    let index = find_pending_redemption(fund, ticket);
+    assert!(fund.redemption_queue[index].investor == ctx.sender(), EUnauthorized);
+
+    let Redemption { ticket: _, investor, shares, deposit_amount } = fund.redemption_queue.remove(index);
+
+    event::emit(RedemptionCancelled {
+        fund_id: object::id(fund),
+        investor,
+        shares,
+        ticket,
+    });
+
+    InvestorPosition {
+        id: object::new(ctx),
+        fund_id: object::id(fund),
+        investor,
+        shares,
+        deposit_amount,
+        entered_at: clock::timestamp_ms(clock),
+        withdrawal_pending: false,
     }
 }
 
+/// Index of the unpaid queue entry holding `ticket`
+///
+/// Tickets increase in queue order and removal keeps that order, so the
+/// pending part of the queue is searched by bisection.
+fun find_pending_redemption(fund: &HedgeFund, ticket: u64): u64 {
+    let mut low = fund.redemption_cursor;
+    let mut high = fund.redemption_queue.length();
+    while (low < high) {
+        let mid = low + (high - low) / 2;
+        let mid_ticket = fund.redemption_queue[mid].ticket;
+        if (mid_ticket == ticket) return mid;
+        if (mid_ticket < ticket) {
+            low = mid + 1
+        } else {
+            high = mid
+        };
+    };
+    abort ERedemptionProcessed
+}
+
+/// Pay out up to `batch_size` queued redemptions, advancing the cursor
+///
+/// Returns the number of redemptions paid. Call repeatedly until
+/// pending_redemptions returns 0; once the queue is drained it is cleared.
+public fun process_redemptions(
+    fund: &mut HedgeFund,
+    batch_size: u64,
+    ctx: &mut TxContext
+): u64 {
+    assert!(fund.state == FUND_SETTLED, EFundNotSettled);
+    assert!(batch_size > 0, EInvalidAmount);
+
+    let queue_len = fund.redemption_queue.length();
+    let mut processed = 0;
+
+    while (processed < batch_size && fund.redemption_cursor < queue_len) {
+        let redemption = &fund.redemption_queue[fund.redemption_cursor];
+        let investor = redemption.investor;
+        let shares = redemption.shares;
+
+        let total_capital = balance::value(&fund.capital_pool);
+        assert!(fund.total_shares >= shares, EInsufficientShares);
+
+        // Same saturating proportional payout as withdraw_shares
+        let amount = payout_for_shares(fund, total_capital, shares);
+
+        fund.total_shares = fund.total_shares - shares;
+        fund.outstanding_positions = fund.outstanding_positions - 1;
+        let payout = coin::from_balance(balance::split(&mut fund.capital_pool, amount), ctx);
+        transfer::public_transfer(payout, investor);
+
+        event::emit(InvestorWithdrew {
+            fund_id: object::id(fund),
+            investor,
+            shares_redeemed: shares,
+            amount_received: amount,
+        });
+
+        fund.redemption_cursor = fund.redemption_cursor + 1;
+        processed = processed + 1;
+    };
+
+    // Drop paid entries once everything queued so far has been processed
+    if (fund.redemption_cursor == queue_len) {
+        fund.redemption_queue = vector[];
+        fund.redemption_cursor = 0;
+    };
+
+    event::emit(RedemptionsProcessed {
+        fund_id: object::id(fund),
+        processed,
+        remaining: pending_redemptions(fund),
+    });
+
+    processed
+}
+
 /// Manager withdraws accumulated fees
+///
+/// For funds created with fees_after_investors, this aborts until every
+/// investor position has been paid out (directly or via the redemption queue).
 public fun withdraw_manager_fees(
     fund: &mut HedgeFund,
     ctx: &mut TxContext
 ): Coin<SUI> {
     assert!(ctx.sender() == fund.manager, EUnauthorized);
     assert!(fund.state == FUND_SETTLED, EFundNotSettled);
+    if (fund.fees_after_investors) {
+        assert!(fund.outstanding_positions == 0, EInvestorsNotRedeemed);
+    };
 
     let amount = balance::value(&fund.manager_fees);
     coin::from_balance(
@@ -1189,6 +1734,64 @@ public fun withdraw_manager_fees(
     )
 }
 
+/// Manager deletes a settled fund once it is fully drained
+///
+/// Aborts unless every position has been paid out and both the capital pool
+/// and the accrued manager fees are empty; deleting the shared object
+/// reclaims its storage.
+public fun close_fund(fund: HedgeFund, ctx: &TxContext) {
+    assert!(ctx.sender() == fund.manager, EUnauthorized);
+    assert!(fund.state == FUND_SETTLED, EFundNotSettled);
+    assert!(fund.outstanding_positions == 0, EFundNotEmpty);
+    assert!(balance::value(&fund.capital_pool) == 0, EFundNotEmpty);
+    assert!(balance::value(&fund.manager_fees) == 0, EFundNotEmpty);
+
+    event::emit(FundClosed {
+        fund_id: object::id(&fund),
+        manager: fund.manager,
+    });
+
+    let HedgeFund {
+        id,
+        name: _,
+        manager: _,
+        apex_service_id: _,
+        state: _,
+        total_shares: _,
+        capital_pool,
+        realized_pnl: _,
+        is_profit: _,
+        management_fee_bps: _,
+        performance_fee_bps: _,
+        entry_fee: _,
+        max_capacity: _,
+        created_at: _,
+        trading_started_at: _,
+        settled_at: _,
+        manager_fees,
+        authorized_managers: _,
+        redemption_queue: _,
+        redemption_cursor: _,
+        next_redemption_ticket: _,
+        fees_after_investors: _,
+        outstanding_positions: _,
+        hurdle_bps: _,
+        starting_capital: _,
+        trader_caps: _,
+        entry_fee_protocol_bps: _,
+        entry_fees_to_protocol: _,
+        entry_fees_to_provider: _,
+        trade_ids: _,
+        min_trade_input: _,
+        share_precision: _,
+        high_water_mark: _,
+    } = fund;
+
+    balance::destroy_zero(capital_pool);
+    balance::destroy_zero(manager_fees);
+    object::delete(id);
+}
+
 // ==================== View Functions ====================
 
 public fun fund_name(fund: &HedgeFund): vector<u8> {
@@ -1211,6 +1814,18 @@ public fun fund_capital(fund: &HedgeFund): u64 {
     balance::value(&fund.capital_pool)
 }
 
+/// Current MIST value of `shares` (shares are scaled by share_precision)
+public fun share_value(fund: &HedgeFund, shares: u64): u64 {
+    if (fund.total_shares == 0) return 0;
+    let value_u128 = ((balance::value(&fund.capital_pool) as u128) * (shares as u128)) / (fund.total_shares as u128);
+    (value_u128 as u64)
+}
+
+/// Shares issued per MIST at inception
+public fun share_precision(fund: &HedgeFund): u64 {
+    fund.share_precision
+}
+
 public fun fund_realized_pnl(fund: &HedgeFund): (u64, bool) {
     (fund.realized_pnl, fund.is_profit)
 }
@@ -1235,6 +1850,83 @@ public fun position_investor(position: &InvestorPosition): address {
     position.investor
 }
 
+public fun fund_outstanding_positions(fund: &HedgeFund): u64 {
+    fund.outstanding_positions
+}
+
+public fun fund_fees_after_investors(fund: &HedgeFund): bool {
+    fund.fees_after_investors
+}
+
+public fun receipt_amount_received(receipt: &SettlementReceipt): u64 {
+    receipt.amount_received
+}
+
+public fun receipt_shares_redeemed(receipt: &SettlementReceipt): u64 {
+    receipt.shares_redeemed
+}
+
+public fun is_trader_cap_active(fund: &HedgeFund, cap_id: ID): bool {
+    vec_set::contains(&fund.trader_caps, &cap_id)
+}
+
+public fun trader_cap_fund_id(cap: &TraderCap): ID {
+    cap.fund_id
+}
+
+public fun trader_cap_trader(cap: &TraderCap): address {
+    cap.trader
+}
+
+public fun fund_hurdle_bps(fund: &HedgeFund): u64 {
+    fund.hurdle_bps
+}
+
+public fun fund_starting_capital(fund: &HedgeFund): u64 {
+    fund.starting_capital
+}
+
+public fun fund_high_water_mark(fund: &HedgeFund): u64 {
+    fund.high_water_mark
+}
+
+public fun fund_entry_fee_protocol_bps(fund: &HedgeFund): u64 {
+    fund.entry_fee_protocol_bps
+}
+
+/// Entry fees this fund has sent to the protocol treasury
+public fun fund_entry_fees_to_protocol(fund: &HedgeFund): u64 {
+    fund.entry_fees_to_protocol
+}
+
+/// Entry fees this fund has routed to its APEX service provider
+public fun fund_entry_fees_to_provider(fund: &HedgeFund): u64 {
+    fund.entry_fees_to_provider
+}
+
+public fun fund_min_trade_input(fund: &HedgeFund): u64 {
+    fund.min_trade_input
+}
+
+/// Number of trades recorded against the fund
+public fun trade_count(fund: &HedgeFund): u64 {
+    vector::length(&fund.trade_ids)
+}
+
+/// ID of the `i`-th TradeRecord issued for the fund (0 = oldest)
+public fun trade_id_at(fund: &HedgeFund, i: u64): ID {
+    *vector::borrow(&fund.trade_ids, i)
+}
+
+/// Queued redemptions not yet paid out
+public fun pending_redemptions(fund: &HedgeFund): u64 {
+    fund.redemption_queue.length() - fund.redemption_cursor
+}
+
+public fun redemption_cursor(fund: &HedgeFund): u64 {
+    fund.redemption_cursor
+}
+
 public fun is_fund_open(fund: &HedgeFund): bool {
     fund.state == FUND_OPEN
 }
@@ -1276,9 +1968,61 @@ public fun create_fund_for_testing(
         settled_at: 0,
         manager_fees: balance::zero(),
         authorized_managers: vec_set::empty(),
+        redemption_queue: vector[],
+        redemption_cursor: 0,
+        next_redemption_ticket: 0,
+        fees_after_investors: false,
+        outstanding_positions: 0,
+        hurdle_bps: 0,
+        starting_capital: 0,
+        trader_caps: vec_set::empty(),
+        entry_fee_protocol_bps: 0,
+        entry_fees_to_protocol: 0,
+        entry_fees_to_provider: 0,
+        trade_ids: vector[],
+        min_trade_input: 0,
+        share_precision: DEFAULT_SHARE_PRECISION,
+        high_water_mark: 0,
     }
 }
 
+#[test_only]
+public fun deposit_for_testing(
+    fund: &mut HedgeFund,
+    deposit: Coin<SUI>,
+    ctx: &mut TxContext
+): InvestorPosition {
+    issue_position(fund, deposit, 0, ctx)
+}
+
+#[test_only]
+public fun set_state_for_testing(fund: &mut HedgeFund, state: u8) {
+    fund.state = state;
+}
+
+#[test_only]
+public fun set_min_trade_input_for_testing(fund: &mut HedgeFund, min_trade_input: u64) {
+    fund.min_trade_input = min_trade_input;
+}
+
+/// Burn up to `loss` MIST of capital and book it as a realized loss, as a
+/// buggy simulated trade could; losses beyond the pool only hit the P&L
+#[test_only]
+public fun simulate_loss_for_testing(fund: &mut HedgeFund, loss: u64) {
+    let capital = balance::value(&fund.capital_pool);
+    let burned = if (loss > capital) { capital } else { loss };
+    balance::destroy_for_testing(balance::split(&mut fund.capital_pool, burned));
+
+    if (!fund.is_profit) {
+        fund.realized_pnl = fund.realized_pnl + loss;
+    } else if (fund.realized_pnl >= loss) {
+        fund.realized_pnl = fund.realized_pnl - loss;
+    } else {
+        fund.realized_pnl = loss - fund.realized_pnl;
+        fund.is_profit = false;
+    };
+}
+
 #[test_only]
 public fun destroy_fund_for_testing(fund: HedgeFund) {
     let HedgeFund {
@@ -1300,6 +2044,21 @@ public fun destroy_fund_for_testing(fund: HedgeFund) {
         settled_at: _,
         manager_fees,
         authorized_managers: _,
+        redemption_queue: _,
+        redemption_cursor: _,
+        next_redemption_ticket: _,
+        fees_after_investors: _,
+        outstanding_positions: _,
+        hurdle_bps: _,
+        starting_capital: _,
+        trader_caps: _,
+        entry_fee_protocol_bps: _,
+        entry_fees_to_protocol: _,
+        entry_fees_to_provider: _,
+        trade_ids: _,
+        min_trade_input: _,
+        share_precision: _,
+        high_water_mark: _,
     } = fund;
 
     balance::destroy_for_testing(capital_pool);
@@ -18,6 +18,7 @@ use sui::balance::{Self, Balance};
 use sui::event;
 use sui::hash;
 use sui::bcs;
+use sui::dynamic_field as df;
 use sui::sui::SUI;
 
 // ==================== Error Codes ====================
@@ -37,6 +38,17 @@ const EInvalidSecret: u64 = 11;
 const ESecretRequired: u64 = 12;
 /// Wallet funding is restricted to owner only
 const EFundingRestricted: u64 = 13;
+/// Purchase was built against an outdated service version
+const EStaleService: u64 = 14;
+/// Per-transaction spend limit exceeds the daily limit
+const EInconsistentLimits: u64 = 15;
+/// Provider already has the maximum number of registered services
+const EServiceLimitReached: u64 = 16;
+/// Purchase attempted outside the service's availability window
+const EServiceUnavailable: u64 = 17;
+/// Service only sells access through a trusted-meter verified session
+/// (apex_workflows::open_verified_access_session)
+const EMeterRequired: u64 = 18;
 
 // ==================== Constants ====================
 const MAX_NAME_LENGTH: u64 = 256;
@@ -44,6 +56,8 @@ const MAX_DESCRIPTION_LENGTH: u64 = 1024;
 const REGISTRATION_FEE: u64 = 100_000_000; // 0.1 SUI
 const MS_PER_DAY: u64 = 86_400_000;
 const MIN_SECRET_HASH_LENGTH: u64 = 32;
+const DEFAULT_MAX_SERVICES_PER_PROVIDER: u64 = 100;
+const U64_MAX: u64 = 18446744073709551615;
 
 // ==================== Admin & Config ====================
 
@@ -68,6 +82,20 @@ public struct ProtocolConfig has key {
     treasury: Balance<SUI>,
     /// Protocol version
     version: u64,
+    /// Cap on services a single provider may register (anti-spam)
+    max_services_per_provider: u64,
+}
+
+/// Dynamic field key on ProtocolConfig for the IDs of a provider's services,
+/// in registration order
+public struct ProviderServicesKey has copy, drop, store {
+    provider: address,
+}
+
+/// Dynamic field key on ProtocolConfig marking a service ID as registered
+/// through this config (used to validate bundle contents)
+public struct RegisteredServiceKey has copy, drop, store {
+    service_id: ID,
 }
 
 // ==================== Service Provider ====================
@@ -90,6 +118,17 @@ public struct ServiceProvider has key {
     revenue: Balance<SUI>,
     /// Whether provider is active
     active: bool,
+    /// Pricing version, bumped on every price update
+    version: u64,
+    /// Purchases allowed from this timestamp (ms, 0 = no start bound)
+    available_from_ms: u64,
+    /// Purchases allowed until this timestamp (ms, 0 = no end bound)
+    available_until_ms: u64,
+    /// Access is only sold through a trusted-meter verified session
+    requires_meter: bool,
+    /// How long (ms) after a capability's nominal expiry use_access still
+    /// accepts it; does not extend the purchasable window
+    grace_ms: u64,
 }
 
 // ==================== Access Capability ====================
@@ -113,6 +152,35 @@ public struct AccessCapability has key, store {
     epoch_usage: u64,
     /// Last epoch number
     last_epoch: u64,
+    /// Whether use_access appends to the usage history dynamic field
+    track_usage: bool,
+}
+
+/// Dynamic field key for a capability's usage history
+public struct UsageHistoryKey has copy, drop, store {}
+
+/// UsageEntry - One recorded consumption of a capability
+public struct UsageEntry has copy, drop, store {
+    /// Clock timestamp when units were consumed (ms)
+    timestamp: u64,
+    /// Units consumed
+    units: u64,
+}
+
+/// BundleCapability - One unit pool shared across several services
+///
+/// Payment is escrowed at purchase; each use pays the target service at its
+/// current price, so one object replaces a capability per service.
+public struct BundleCapability has key, store {
+    id: UID,
+    /// Services this bundle can be used against
+    services: vector<ID>,
+    /// Units remaining across all included services
+    remaining_units: u64,
+    /// Escrowed payment drawn down on each use
+    escrow: Balance<SUI>,
+    /// Expiry timestamp (0 = no expiry)
+    expires_at: u64,
 }
 
 // ==================== Streaming Payments ====================
@@ -228,6 +296,13 @@ public struct AccessPurchased has copy, drop {
     cost: u64,
 }
 
+public struct AccessExtended has copy, drop {
+    capability_id: ID,
+    service_id: ID,
+    expires_at: u64,
+    cost: u64,
+}
+
 public struct AccessUsed has copy, drop {
     capability_id: ID,
     service_id: ID,
@@ -235,6 +310,14 @@ public struct AccessUsed has copy, drop {
     remaining: u64,
 }
 
+public struct BundlePurchased has copy, drop {
+    bundle_id: ID,
+    buyer: address,
+    services: vector<ID>,
+    total_units: u64,
+    escrow: u64,
+}
+
 public struct StreamOpened has copy, drop {
     stream_id: ID,
     consumer: address,
@@ -320,6 +403,7 @@ fun init(_witness: APEX_PAYMENTS, ctx: &mut TxContext) {
         fee_bps: 50, // 0.5% protocol fee
         treasury: balance::zero(),
         version: 1,
+        max_services_per_provider: DEFAULT_MAX_SERVICES_PER_PROVIDER,
     };
 
     event::emit(ProtocolInitialized {
@@ -352,6 +436,24 @@ public fun set_protocol_fee(
     config.fee_bps = new_fee_bps;
 }
 
+/// Set how many services a single provider may register (admin only)
+///
+/// Lowering the cap below a provider's current count does not remove
+/// anything; it only blocks further registrations.
+public fun set_max_services_per_provider(
+    _admin: &AdminCap,
+    config: &mut ProtocolConfig,
+    max_services: u64,
+) {
+    assert!(max_services > 0, EInvalidInput);
+    config.max_services_per_provider = max_services;
+}
+
+/// Add a fee collected elsewhere in the protocol (e.g. fund entry fees) to the treasury
+public fun deposit_protocol_fee(config: &mut ProtocolConfig, fee: Coin<SUI>) {
+    balance::join(&mut config.treasury, coin::into_balance(fee));
+}
+
 /// Withdraw treasury (admin only)
 public fun withdraw_treasury(
     _admin: &AdminCap,
@@ -360,6 +462,7 @@ public fun withdraw_treasury(
     recipient: address,
     ctx: &mut TxContext
 ) {
+    assert!(recipient != @0x0, EInvalidInput);
     assert!(balance::value(&config.treasury) >= amount, EInsufficientBalance);
     let withdrawn = coin::from_balance(
         balance::split(&mut config.treasury, amount),
@@ -371,7 +474,6 @@ public fun withdraw_treasury(
 // ==================== Service Provider Functions ====================
 
 /// Register a new service (x402-style API endpoint)
-#[allow(lint(self_transfer))]
 public fun register_service(
     config: &mut ProtocolConfig,
     name: vector<u8>,
@@ -380,10 +482,146 @@ public fun register_service(
     registration_payment: Coin<SUI>,
     ctx: &mut TxContext
 ) {
+    register_service_with_availability(
+        config,
+        name,
+        description,
+        price_per_unit,
+        0,
+        0,
+        registration_payment,
+        ctx
+    )
+}
+
+/// Register a service that can only be purchased inside [available_from_ms, available_until_ms]
+///
+/// Either bound may be 0 to leave that side open.
+public fun register_service_with_availability(
+    config: &mut ProtocolConfig,
+    name: vector<u8>,
+    description: vector<u8>,
+    price_per_unit: u64,
+    available_from_ms: u64,
+    available_until_ms: u64,
+    registration_payment: Coin<SUI>,
+    ctx: &mut TxContext
+) {
+    register_service_internal(
+        config,
+        name,
+        description,
+        price_per_unit,
+        available_from_ms,
+        available_until_ms,
+        false,
+        registration_payment,
+        ctx
+    )
+}
+
+/// Register a service (Seal/Nautilus style) whose access can only be bought
+/// through a trusted-meter verified session; plain purchase_access,
+/// agent_purchase_access and use_bundle abort with EMeterRequired
+public fun register_metered_service(
+    config: &mut ProtocolConfig,
+    name: vector<u8>,
+    description: vector<u8>,
+    price_per_unit: u64,
+    registration_payment: Coin<SUI>,
+    ctx: &mut TxContext
+) {
+    register_service_internal(
+        config,
+        name,
+        description,
+        price_per_unit,
+        0,
+        0,
+        true,
+        registration_payment,
+        ctx
+    )
+}
+
+#[allow(lint(self_transfer))]
+fun register_service_internal(
+    config: &mut ProtocolConfig,
+    name: vector<u8>,
+    description: vector<u8>,
+    price_per_unit: u64,
+    available_from_ms: u64,
+    available_until_ms: u64,
+    requires_meter: bool,
+    registration_payment: Coin<SUI>,
+    ctx: &mut TxContext
+) {
+    let service = new_service_internal(
+        config,
+        name,
+        description,
+        price_per_unit,
+        available_from_ms,
+        available_until_ms,
+        requires_meter,
+        registration_payment,
+        ctx
+    );
+    transfer::share_object(service);
+}
+
+/// Register a service like `register_service` but hand it back unshared, so
+/// the same PTB can use it (e.g. `list_service`) before `share_service`
+public fun new_service(
+    config: &mut ProtocolConfig,
+    name: vector<u8>,
+    description: vector<u8>,
+    price_per_unit: u64,
+    registration_payment: Coin<SUI>,
+    ctx: &mut TxContext
+): ServiceProvider {
+    new_service_internal(
+        config,
+        name,
+        description,
+        price_per_unit,
+        0,
+        0,
+        false,
+        registration_payment,
+        ctx
+    )
+}
+
+/// Share a service created by `new_service`
+public fun share_service(service: ServiceProvider) {
+    transfer::share_object(service);
+}
+
+fun new_service_internal(
+    config: &mut ProtocolConfig,
+    name: vector<u8>,
+    description: vector<u8>,
+    price_per_unit: u64,
+    available_from_ms: u64,
+    available_until_ms: u64,
+    requires_meter: bool,
+    registration_payment: Coin<SUI>,
+    ctx: &mut TxContext
+): ServiceProvider {
     assert!(!config.paused, EProtocolPaused);
     assert!(vector::length(&name) > 0 && vector::length(&name) <= MAX_NAME_LENGTH, EInvalidInput);
     assert!(vector::length(&description) <= MAX_DESCRIPTION_LENGTH, EInvalidInput);
     assert!(price_per_unit > 0, EInvalidInput);
+    assert!(available_until_ms == 0 || available_until_ms > available_from_ms, EInvalidInput);
+
+    // Per-provider cap, counted from the provider's service index on the config
+    let services_key = ProviderServicesKey { provider: ctx.sender() };
+    if (!df::exists_(&config.id, services_key)) {
+        df::add(&mut config.id, services_key, vector<ID>[]);
+    };
+    let registered: &vector<ID> = df::borrow(&config.id, services_key);
+    assert!(registered.length() < config.max_services_per_provider, EServiceLimitReached);
 
     // Collect registration fee
     let payment_amount = coin::value(&registration_payment);
@@ -408,8 +646,17 @@ public fun register_service(
         total_served: 0,
         revenue: balance::zero(),
         active: true,
+        version: 1,
+        available_from_ms,
+        available_until_ms,
+        requires_meter,
+        grace_ms: 0,
     };
 
+    let services: &mut vector<ID> = df::borrow_mut(&mut config.id, services_key);
+    services.push_back(object::id(&service));
+    df::add(&mut config.id, RegisteredServiceKey { service_id: object::id(&service) }, true);
+
     event::emit(ServiceRegistered {
         service_id: object::id(&service),
         provider: ctx.sender(),
@@ -417,7 +664,7 @@ public fun register_service(
         price_per_unit,
     });
 
-    transfer::share_object(service);
+    service
 }
 
 /// Deactivate service (provider only)
@@ -450,6 +697,21 @@ public fun reactivate_service(
     });
 }
 
+/// Set how long (ms) after a capability's nominal expiry use_access still
+/// accepts it, so an in-flight request isn't cut off (provider only; 0 =
+/// strict expiry)
+///
+/// Only tolerates overruns: availability windows and capabilities'
+/// expires_at are unchanged.
+public fun set_service_grace_ms(
+    service: &mut ServiceProvider,
+    grace_ms: u64,
+    ctx: &TxContext
+) {
+    assert!(ctx.sender() == service.provider, EUnauthorized);
+    service.grace_ms = grace_ms;
+}
+
 /// Update service price (provider only)
 public fun update_service_price(
     service: &mut ServiceProvider,
@@ -459,6 +721,7 @@ public fun update_service_price(
     assert!(ctx.sender() == service.provider, EUnauthorized);
     assert!(new_price > 0, EInvalidInput);
     service.price_per_unit = new_price;
+    service.version = service.version + 1;
 
     event::emit(ServiceUpdated {
         service_id: object::id(service),
@@ -491,7 +754,9 @@ public fun withdraw_revenue(
 /// This is the equivalent of paying for an API and receiving a receipt.
 /// On Sui, the receipt is an AccessCapability object that can be used
 /// in the SAME PTB to access the service - enabling atomic pay-and-use.
-#[allow(lint(self_transfer))]
+///
+/// Aborts with EMeterRequired for services registered with
+/// register_metered_service; those sell through open_verified_access_session.
 public fun purchase_access(
     config: &mut ProtocolConfig,
     service: &mut ServiceProvider,
@@ -502,35 +767,86 @@ public fun purchase_access(
     clock: &Clock,
     ctx: &mut TxContext
 ): AccessCapability {
-    assert!(!config.paused, EProtocolPaused);
-    assert!(service.active, EServiceInactive);
-    assert!(units > 0, EInvalidInput);
+    assert!(!service.requires_meter, EMeterRequired);
+    purchase_access_internal(config, service, payment, units, duration_ms, rate_limit, clock, ctx)
+}
 
-    // Calculate cost with overflow protection
-    let cost = safe_mul(service.price_per_unit, units);
+/// purchase_access for the verified-session workflow: the caller has already
+/// checked a trusted meter, so metered services are allowed
+public(package) fun purchase_metered_access(
+    config: &mut ProtocolConfig,
+    service: &mut ServiceProvider,
+    meter: &TrustedMeter,
+    payment: Coin<SUI>,
+    units: u64,
+    duration_ms: u64,
+    clock: &Clock,
+    ctx: &mut TxContext
+): AccessCapability {
+    assert!(meter.active, EUnauthorized);
+    purchase_access_internal(config, service, payment, units, duration_ms, 0, clock, ctx)
+}
 
+/// Charge `cost` out of `payment`: the protocol fee goes to the treasury, the
+/// rest to the service's revenue, and any excess back to the sender
+#[allow(lint(self_transfer))]
+fun take_payment(
+    config: &mut ProtocolConfig,
+    service: &mut ServiceProvider,
+    payment: Coin<SUI>,
+    cost: u64,
+    ctx: &mut TxContext
+) {
     let payment_amount = coin::value(&payment);
     assert!(payment_amount >= cost, EInsufficientBalance);
 
     // Split payment: protocol fee + provider revenue
     let mut payment_balance = coin::into_balance(payment);
-
-    let fee_amount = (cost * config.fee_bps) / 10000;
-    if (fee_amount > 0) {
-        let fee_balance = balance::split(&mut payment_balance, fee_amount);
-        balance::join(&mut config.treasury, fee_balance);
-    };
+    split_fee(config, service, &mut payment_balance, cost);
 
     // Refund excess
     if (payment_amount > cost) {
-        let refund = coin::from_balance(
-            balance::split(&mut payment_balance, payment_amount - cost),
-            ctx
-        );
-        transfer::public_transfer(refund, ctx.sender());
+        transfer::public_transfer(coin::from_balance(payment_balance, ctx), ctx.sender());
+    } else {
+        balance::destroy_zero(payment_balance);
     };
+}
+
+/// Move `cost` out of `funds`: the protocol fee goes to the treasury and the
+/// rest to the service's revenue
+fun split_fee(
+    config: &mut ProtocolConfig,
+    service: &mut ServiceProvider,
+    funds: &mut Balance<SUI>,
+    cost: u64,
+) {
+    let mut payment = balance::split(funds, cost);
+    let fee_amount = safe_mul(cost, config.fee_bps) / 10000;
+    if (fee_amount > 0) {
+        balance::join(&mut config.treasury, balance::split(&mut payment, fee_amount));
+    };
+    balance::join(&mut service.revenue, payment);
+}
+
+fun purchase_access_internal(
+    config: &mut ProtocolConfig,
+    service: &mut ServiceProvider,
+    payment: Coin<SUI>,
+    units: u64,
+    duration_ms: u64,
+    rate_limit: u64,
+    clock: &Clock,
+    ctx: &mut TxContext
+): AccessCapability {
+    assert!(!config.paused, EProtocolPaused);
+    assert!(service.active, EServiceInactive);
+    assert!(units > 0, EInvalidInput);
+    assert!(is_available(service, clock::timestamp_ms(clock)), EServiceUnavailable);
 
-    balance::join(&mut service.revenue, payment_balance);
+    // Calculate cost with overflow protection
+    let cost = safe_mul(service.price_per_unit, units);
+
+    take_payment(config, service, payment, cost, ctx);
     service.total_served = service.total_served + units;
 
     let expires_at = if (duration_ms > 0) {
@@ -547,6 +863,7 @@ public fun purchase_access(
         rate_limit,
         epoch_usage: 0,
         last_epoch: ctx.epoch(),
+        track_usage: false,
     };
 
     event::emit(AccessPurchased {
@@ -560,6 +877,60 @@ public fun purchase_access(
     capability
 }
 
+/// Purchase access, aborting if the service's price changed since the client read it
+///
+/// Clients pass the service version they quoted against; if the provider has
+/// updated the price in the meantime the purchase aborts with EStaleService
+/// instead of charging an amount the buyer never saw.
+public fun purchase_access_at_version(
+    config: &mut ProtocolConfig,
+    service: &mut ServiceProvider,
+    expected_version: u64,
+    payment: Coin<SUI>,
+    units: u64,
+    duration_ms: u64,
+    rate_limit: u64,
+    clock: &Clock,
+    ctx: &mut TxContext
+): AccessCapability {
+    assert!(expected_version == service.version, EStaleService);
+    purchase_access(config, service, payment, units, duration_ms, rate_limit, clock, ctx)
+}
+
+/// Push a capability's expiry `additional_ms` further out
+///
+/// Must happen before the capability expires (grace included); a lapsed one
+/// aborts with EExpired and has to be bought again. Renewal costs one unit at
+/// the service's current price, split like a purchase. Capabilities without
+/// an expiry (expires_at == 0) have nothing to extend and abort EInvalidInput.
+public fun extend_access(
+    config: &mut ProtocolConfig,
+    service: &mut ServiceProvider,
+    cap: &mut AccessCapability,
+    payment: Coin<SUI>,
+    additional_ms: u64,
+    clock: &Clock,
+    ctx: &mut TxContext
+) {
+    assert!(!config.paused, EProtocolPaused);
+    assert!(cap.service_id == object::id(service), EInvalidCapability);
+    assert!(service.active, EServiceInactive);
+    assert!(additional_ms > 0 && cap.expires_at > 0, EInvalidInput);
+    assert!(!is_expired_with_grace(cap, service.grace_ms, clock), EExpired);
+    assert!(cap.expires_at <= U64_MAX - additional_ms, EOverflow);
+
+    let cost = service.price_per_unit;
+    take_payment(config, service, payment, cost, ctx);
+    cap.expires_at = cap.expires_at + additional_ms;
+
+    event::emit(AccessExtended {
+        capability_id: object::id(cap),
+        service_id: cap.service_id,
+        expires_at: cap.expires_at,
+        cost,
+    });
+}
+
 /// Use access capability - consume units from a capability
 /// Returns true if access granted, aborts otherwise
 public fun use_access(
@@ -572,10 +943,7 @@ public fun use_access(
     assert!(cap.service_id == object::id(service), EInvalidCapability);
     assert!(service.active, EServiceInactive);
 
-    // Check expiry
-    if (cap.expires_at > 0) {
-        assert!(clock::timestamp_ms(clock) <= cap.expires_at, EExpired);
-    };
+    assert!(!is_expired_with_grace(cap, service.grace_ms, clock), EExpired);
 
     assert!(cap.remaining_units >= units, EInsufficientBalance);
 
@@ -593,6 +961,14 @@ public fun use_access(
     cap.remaining_units = cap.remaining_units - units;
     cap.epoch_usage = cap.epoch_usage + units;
 
+    if (cap.track_usage) {
+        let history: &mut vector<UsageEntry> = df::borrow_mut(&mut cap.id, UsageHistoryKey {});
+        history.push_back(UsageEntry {
+            timestamp: clock::timestamp_ms(clock),
+            units,
+        });
+    };
+
     event::emit(AccessUsed {
         capability_id: object::id(cap),
         service_id: cap.service_id,
@@ -617,20 +993,153 @@ public fun verify_access(
     true
 }
 
+/// Enable usage history on a capability
+///
+/// Opt-in because every tracked use_access grows the capability's storage.
+/// The history lives in a dynamic field so it can be read off-chain.
+public fun enable_usage_history(cap: &mut AccessCapability) {
+    if (cap.track_usage) return;
+    cap.track_usage = true;
+    if (!df::exists_(&cap.id, UsageHistoryKey {})) {
+        df::add(&mut cap.id, UsageHistoryKey {}, vector<UsageEntry>[]);
+    };
+}
+
+/// Stop recording usage history (existing entries are kept)
+public fun disable_usage_history(cap: &mut AccessCapability) {
+    cap.track_usage = false;
+}
+
 /// Burn unused capability
 public fun burn_capability(cap: AccessCapability) {
     let AccessCapability {
-        id,
+        mut id,
         service_id: _,
         remaining_units: _,
         expires_at: _,
         rate_limit: _,
         epoch_usage: _,
         last_epoch: _,
+        track_usage: _,
     } = cap;
+    if (df::exists_(&id, UsageHistoryKey {})) {
+        let _: vector<UsageEntry> = df::remove(&mut id, UsageHistoryKey {});
+    };
     object::delete(id);
 }
 
+// ==================== Bundle Capability Functions ====================
+
+/// Purchase a bundle covering several services with one shared unit pool
+///
+/// The payment is escrowed in the bundle and drawn down by use_bundle at each
+/// service's price; whatever is left can be reclaimed with close_bundle.
+public fun purchase_bundle(
+    config: &ProtocolConfig,
+    services: vector<ID>,
+    payment: Coin<SUI>,
+    total_units: u64,
+    duration_ms: u64,
+    clock: &Clock,
+    ctx: &mut TxContext
+): BundleCapability {
+    assert!(!config.paused, EProtocolPaused);
+    assert!(!services.is_empty(), EInvalidInput);
+    assert!(total_units > 0, EInvalidInput);
+
+    // Every entry must be a distinct service registered through this config
+    let mut i = 0;
+    while (i < services.length()) {
+        let service_id = services[i];
+        assert!(
+            df::exists_(&config.id, RegisteredServiceKey { service_id }),
+            EInvalidInput
+        );
+        let mut j = i + 1;
+        while (j < services.length()) {
+            assert!(services[j] != service_id, EInvalidInput);
+            j = j + 1;
+        };
+        i = i + 1;
+    };
+
+    let expires_at = if (duration_ms > 0) {
+        clock::timestamp_ms(clock) + duration_ms
+    } else {
+        0
+    };
+
+    let bundle = BundleCapability {
+        id: object::new(ctx),
+        services,
+        remaining_units: total_units,
+        escrow: coin::into_balance(payment),
+        expires_at,
+    };
+
+    event::emit(BundlePurchased {
+        bundle_id: object::id(&bundle),
+        buyer: ctx.sender(),
+        services: bundle.services,
+        total_units,
+        escrow: balance::value(&bundle.escrow),
+    });
+
+    bundle
+}
+
+/// Use units from a bundle against one of its included services
+public fun use_bundle(
+    bundle: &mut BundleCapability,
+    config: &mut ProtocolConfig,
+    service: &mut ServiceProvider,
+    units: u64,
+    clock: &Clock,
+): bool {
+    assert!(!config.paused, EProtocolPaused);
+    let service_id = object::id(service);
+    assert!(bundle.services.contains(&service_id), EInvalidCapability);
+    assert!(service.active, EServiceInactive);
+    assert!(!service.requires_meter, EMeterRequired);
+    assert!(is_available(service, clock::timestamp_ms(clock)), EServiceUnavailable);
+
+    if (bundle.expires_at > 0) {
+        assert!(clock::timestamp_ms(clock) <= bundle.expires_at, EExpired);
+    };
+
+    assert!(bundle.remaining_units >= units, EInsufficientBalance);
+
+    let cost = safe_mul(service.price_per_unit, units);
+    assert!(balance::value(&bundle.escrow) >= cost, EInsufficientBalance);
+
+    split_fee(config, service, &mut bundle.escrow, cost);
+
+    service.total_served = service.total_served + units;
+    bundle.remaining_units = bundle.remaining_units - units;
+
+    event::emit(AccessUsed {
+        capability_id: object::id(bundle),
+        service_id,
+        units_used: units,
+        remaining: bundle.remaining_units,
+    });
+
+    true
+}
+
+/// Close a bundle and reclaim the unspent escrow
+public fun close_bundle(bundle: BundleCapability, ctx: &mut TxContext): Coin<SUI> {
+    let BundleCapability {
+        id,
+        services: _,
+        remaining_units: _,
+        escrow,
+        expires_at: _,
+    } = bundle;
+    object::delete(id);
+    coin::from_balance(escrow, ctx)
+}
+
 // ==================== Streaming Payment Functions ====================
 
 /// Open a payment stream for continuous micropayments
@@ -804,6 +1313,7 @@ public fun agent_purchase_access(
     assert!(!wallet.paused, EUnauthorized);
     assert!(!config.paused, EProtocolPaused);
     assert!(service.active, EServiceInactive);
+    assert!(!service.requires_meter, EMeterRequired);
 
     // Update daily limit tracking
     let now = clock::timestamp_ms(clock);
@@ -818,18 +1328,8 @@ public fun agent_purchase_access(
     assert!(wallet.daily_spent + cost <= wallet.daily_limit, EExceededLimit);
     assert!(balance::value(&wallet.balance) >= cost, EInsufficientBalance);
 
-    // Extract payment from wallet
-    let payment_balance = balance::split(&mut wallet.balance, cost);
-
-    // Protocol fee
-    let fee_amount = (cost * config.fee_bps) / 10000;
-    let mut payment_balance = payment_balance;
-    if (fee_amount > 0) {
-        let fee_balance = balance::split(&mut payment_balance, fee_amount);
-        balance::join(&mut config.treasury, fee_balance);
-    };
-
-    balance::join(&mut service.revenue, payment_balance);
+    // Pay from the wallet: protocol fee + provider revenue
+    split_fee(config, service, &mut wallet.balance, cost);
 
     wallet.daily_spent = wallet.daily_spent + cost;
     wallet.nonce = wallet.nonce + 1;
@@ -849,6 +1349,7 @@ public fun agent_purchase_access(
         rate_limit,
         epoch_usage: 0,
         last_epoch: ctx.epoch(),
+        track_usage: false,
     }
 }
 
@@ -1097,10 +1598,56 @@ public fun capability_expires_at(cap: &AccessCapability): u64 {
     cap.expires_at
 }
 
+/// Whether the capability is past its nominal expires_at (0 = never expires)
+///
+/// Ignores the service's grace period; use is_expired_with_grace with
+/// service_grace_ms for the check use_access and extend_access actually
+/// apply.
+public fun is_expired(cap: &AccessCapability, clock: &Clock): bool {
+    cap.expires_at > 0 && clock::timestamp_ms(clock) > cap.expires_at
+}
+
+/// Expiry as use_access sees it: past expires_at + grace_ms
+public fun is_expired_with_grace(cap: &AccessCapability, grace_ms: u64, clock: &Clock): bool {
+    if (cap.expires_at == 0) return false;
+    let deadline = (cap.expires_at as u128) + (grace_ms as u128);
+    (clock::timestamp_ms(clock) as u128) > deadline
+}
+
 public fun capability_service_id(cap: &AccessCapability): ID {
     cap.service_id
 }
 
+public fun capability_tracks_usage(cap: &AccessCapability): bool {
+    cap.track_usage
+}
+
+/// Number of recorded usage entries (0 if history was never enabled)
+public fun usage_history_length(cap: &AccessCapability): u64 {
+    if (!df::exists_(&cap.id, UsageHistoryKey {})) return 0;
+    let history: &vector<UsageEntry> = df::borrow(&cap.id, UsageHistoryKey {});
+    history.length()
+}
+
+/// Get a usage entry by index: (timestamp, units)
+public fun usage_history_entry(cap: &AccessCapability, idx: u64): (u64, u64) {
+    let history: &vector<UsageEntry> = df::borrow(&cap.id, UsageHistoryKey {});
+    let entry = &history[idx];
+    (entry.timestamp, entry.units)
+}
+
+public fun bundle_remaining(bundle: &BundleCapability): u64 {
+    bundle.remaining_units
+}
+
+public fun bundle_escrow(bundle: &BundleCapability): u64 {
+    balance::value(&bundle.escrow)
+}
+
+public fun bundle_includes(bundle: &BundleCapability, service_id: ID): bool {
+    bundle.services.contains(&service_id)
+}
+
 public fun stream_remaining_escrow(stream: &PaymentStream): u64 {
     balance::value(&stream.escrow)
 }
@@ -1125,6 +1672,30 @@ public fun service_price(service: &ServiceProvider): u64 {
     service.price_per_unit
 }
 
+public fun service_version(service: &ServiceProvider): u64 {
+    service.version
+}
+
+/// Whether `now_ms` falls inside the service's availability window
+public fun is_available(service: &ServiceProvider, now_ms: u64): bool {
+    now_ms >= service.available_from_ms
+        && (service.available_until_ms == 0 || now_ms <= service.available_until_ms)
+}
+
+public fun service_availability(service: &ServiceProvider): (u64, u64) {
+    (service.available_from_ms, service.available_until_ms)
+}
+
+/// Grace period (ms) use_access allows past a capability's expiry
+public fun service_grace_ms(service: &ServiceProvider): u64 {
+    service.grace_ms
+}
+
+/// True if access is only sold through a trusted-meter verified session
+public fun service_requires_meter(service: &ServiceProvider): bool {
+    service.requires_meter
+}
+
 public fun service_is_active(service: &ServiceProvider): bool {
     service.active
 }
@@ -1133,10 +1704,41 @@ public fun service_total_served(service: &ServiceProvider): u64 {
     service.total_served
 }
 
+public fun service_revenue(service: &ServiceProvider): u64 {
+    balance::value(&service.revenue)
+}
+
 public fun protocol_is_paused(config: &ProtocolConfig): bool {
     config.paused
 }
 
+public fun protocol_fee_bps(config: &ProtocolConfig): u64 {
+    config.fee_bps
+}
+
+public fun treasury_balance(config: &ProtocolConfig): u64 {
+    balance::value(&config.treasury)
+}
+
+public fun max_services_per_provider(config: &ProtocolConfig): u64 {
+    config.max_services_per_provider
+}
+
+/// Services registered by `provider` so far
+public fun provider_service_count(config: &ProtocolConfig, provider: address): u64 {
+    let key = ProviderServicesKey { provider };
+    if (!df::exists_(&config.id, key)) return 0;
+    let services: &vector<ID> = df::borrow(&config.id, key);
+    services.length()
+}
+
+/// IDs of the services `provider` registered, oldest first
+public fun services_of(config: &ProtocolConfig, provider: address): vector<ID> {
+    let key = ProviderServicesKey { provider };
+    if (!df::exists_(&config.id, key)) return vector[];
+    *df::borrow(&config.id, key)
+}
+
 // ==================== Delegated Agent Authorization ====================
 
 /// Authorization from human owner to agent address
@@ -1185,6 +1787,8 @@ public fun create_authorization(
     clock: &Clock,
     ctx: &mut TxContext
 ): AgentAuthorization {
+    assert_limits_consistent(spend_limit_per_tx, daily_limit);
+
     let expires_at = if (duration_ms == 0) {
         0
     } else {
@@ -1214,6 +1818,14 @@ public fun create_authorization(
     auth
 }
 
+/// A per-tx limit above the daily limit can never be reached, so reject it
+/// (0 means unlimited for either limit)
+fun assert_limits_consistent(spend_limit_per_tx: u64, daily_limit: u64) {
+    if (spend_limit_per_tx > 0 && daily_limit > 0) {
+        assert!(spend_limit_per_tx <= daily_limit, EInconsistentLimits);
+    };
+}
+
 /// Agent purchases access using authorization
 #[allow(lint(self_transfer))]
 public fun authorized_purchase(
@@ -1313,6 +1925,9 @@ public fun revoke_authorization(auth: AgentAuthorization, ctx: &TxContext) {
 }
 
 /// Owner updates authorization limits
+///
+/// A daily limit below what was already spent this epoch is accepted; it just
+/// blocks further purchases until the next epoch resets `daily_spent`.
 public fun update_authorization_limits(
     auth: &mut AgentAuthorization,
     spend_limit_per_tx: u64,
@@ -1320,6 +1935,7 @@ public fun update_authorization_limits(
     ctx: &TxContext
 ) {
     assert!(ctx.sender() == auth.owner, EUnauthorized);
+    assert_limits_consistent(spend_limit_per_tx, daily_limit);
     auth.spend_limit_per_tx = spend_limit_per_tx;
     auth.daily_limit = daily_limit;
 
@@ -1330,6 +1946,27 @@ public fun update_authorization_limits(
     });
 }
 
+/// Owner raises the daily limit by `additional` without touching today's spend
+///
+/// Aborts on an unlimited (0) daily limit, since there is nothing to raise;
+/// lowering goes through update_authorization_limits.
+public fun increase_daily_limit(
+    auth: &mut AgentAuthorization,
+    additional: u64,
+    ctx: &TxContext
+) {
+    assert!(ctx.sender() == auth.owner, EUnauthorized);
+    assert!(additional > 0 && auth.daily_limit > 0, EInvalidInput);
+    assert!(auth.daily_limit <= U64_MAX - additional, EOverflow);
+    auth.daily_limit = auth.daily_limit + additional;
+
+    event::emit(AuthorizationLimitsUpdated {
+        auth_id: object::id(auth),
+        spend_limit_per_tx: auth.spend_limit_per_tx,
+        daily_limit: auth.daily_limit,
+    });
+}
+
 /// Owner adds allowed service
 public fun add_allowed_service(
     auth: &mut AgentAuthorization,
@@ -1432,6 +2069,126 @@ public fun record_verified_consumption(
     signature: vector<u8>,
     clock: &Clock,
     _ctx: &mut TxContext
+) {
+    apply_verified_report(stream, service, meter, units, timestamp, signature, clock);
+}
+
+/// Consume several TEE-verified reports for one stream in a single call.
+/// `units`, `timestamps` and `signatures` are parallel; if any signature
+/// fails to verify the whole batch aborts and nothing is consumed.
+public fun record_verified_consumption_batch(
+    stream: &mut PaymentStream,
+    service: &mut ServiceProvider,
+    meter: &TrustedMeter,
+    units: vector<u64>,
+    timestamps: vector<u64>,
+    signatures: vector<vector<u8>>,
+    clock: &Clock,
+    _ctx: &mut TxContext
+) {
+    let count = vector::length(&units);
+    assert!(count > 0, EInvalidInput);
+    assert!(vector::length(&timestamps) == count, EInvalidInput);
+    assert!(vector::length(&signatures) == count, EInvalidInput);
+
+    let mut i = 0;
+    while (i < count) {
+        apply_verified_report(
+            stream,
+            service,
+            meter,
+            *vector::borrow(&units, i),
+            *vector::borrow(&timestamps, i),
+            *vector::borrow(&signatures, i),
+            clock
+        );
+        i = i + 1;
+    };
+}
+
+/// One TEE-signed usage report for close_verified_sessions_batch
+public struct SessionReport has copy, drop, store {
+    units: u64,
+    timestamp: u64,
+}
+
+public fun new_session_report(units: u64, timestamp: u64): SessionReport {
+    SessionReport { units, timestamp }
+}
+
+/// Close several verified access sessions for one service in a single call.
+/// `caps`, `reports` and `signatures` are parallel; each signature covers
+/// capability_id || units || timestamp. If any report fails to verify or its
+/// units cannot be consumed the whole call aborts and no capability changes.
+/// The capabilities are returned to the sender.
+#[allow(lint(self_transfer))]
+public fun close_verified_sessions_batch(
+    mut caps: vector<AccessCapability>,
+    service: &ServiceProvider,
+    meter: &TrustedMeter,
+    reports: vector<SessionReport>,
+    signatures: vector<vector<u8>>,
+    clock: &Clock,
+    ctx: &TxContext
+) {
+    let count = vector::length(&caps);
+    assert!(count > 0, EInvalidInput);
+    assert!(vector::length(&reports) == count, EInvalidInput);
+    assert!(vector::length(&signatures) == count, EInvalidInput);
+
+    let mut i = 0;
+    while (i < count) {
+        let report = *vector::borrow(&reports, i);
+        let cap = vector::borrow_mut(&mut caps, i);
+        verify_session_report(cap, meter, report, *vector::borrow(&signatures, i), clock);
+        use_access(cap, service, report.units, clock, ctx);
+        i = i + 1;
+    };
+
+    while (!vector::is_empty(&caps)) {
+        transfer::public_transfer(vector::pop_back(&mut caps), ctx.sender());
+    };
+    vector::destroy_empty(caps);
+}
+
+/// Check a report was signed by the meter for this capability and is recent
+fun verify_session_report(
+    cap: &AccessCapability,
+    meter: &TrustedMeter,
+    report: SessionReport,
+    signature: vector<u8>,
+    clock: &Clock,
+) {
+    use sui::ed25519;
+
+    assert!(meter.active, EUnauthorized);
+
+    // Build the message that was signed: capability_id || units || timestamp
+    let mut message = object::id(cap).to_bytes();
+    vector::append(&mut message, bcs::to_bytes(&report.units));
+    vector::append(&mut message, bcs::to_bytes(&report.timestamp));
+
+    let is_valid = ed25519::ed25519_verify(
+        &signature,
+        &meter.enclave_pubkey,
+        &message
+    );
+    assert!(is_valid, EUnauthorized);
+
+    // Verify timestamp is recent (within 5 minutes)
+    let now = clock::timestamp_ms(clock);
+    assert!(now >= report.timestamp && now - report.timestamp < 300_000, EExpired);
+}
+
+/// Verify one signed report and move its cost from escrow to the provider
+fun apply_verified_report(
+    stream: &mut PaymentStream,
+    service: &mut ServiceProvider,
+    meter: &TrustedMeter,
+    units: u64,
+    timestamp: u64,
+    signature: vector<u8>,
+    clock: &Clock,
 ) {
     use sui::ed25519;
 
@@ -1490,7 +2247,7 @@ public fun authorization_agent(auth: &AgentAuthorization): address {
 public fun authorization_daily_remaining(auth: &AgentAuthorization): u64 {
     if (auth.daily_limit == 0) {
         // Unlimited
-        18446744073709551615 // u64::MAX
+        U64_MAX
     } else if (auth.daily_spent >= auth.daily_limit) {
         0
     } else {
@@ -1743,6 +2500,7 @@ public fun initialize_protocol(ctx: &mut TxContext) {
         fee_bps: 50, // 0.5% protocol fee
         treasury: balance::zero(),
         version: 1,
+        max_services_per_provider: DEFAULT_MAX_SERVICES_PER_PROVIDER,
     };
 
     event::emit(ProtocolInitialized {
@@ -119,6 +119,10 @@ fun check_access_policy(
 /// the key servers provide the decryption key.
 ///
 /// This enforces: "Only AccessCapability holders can decrypt service content"
+///
+/// Non-mutation contract: key servers evaluate this with a dry run, so it must
+/// only read its inputs. Every object is taken by immutable reference and the
+/// function creates no objects and emits no events - keep it that way.
 entry fun seal_approve(
     content_id: vector<u8>,
     pkg_version: &PackageVersion,
@@ -134,7 +138,8 @@ entry fun seal_approve(
 
 /// Seal approval that also verifies minimum remaining units
 ///
-/// Use this when content requires a certain "weight" of access
+/// Use this when content requires a certain "weight" of access.
+/// Same non-mutation contract as seal_approve.
 entry fun seal_approve_with_units(
     content_id: vector<u8>,
     pkg_version: &PackageVersion,
@@ -191,6 +196,17 @@ public fun init_for_testing(ctx: &mut TxContext) {
     init(ctx)
 }
 
+#[test_only]
+public fun seal_approve_for_testing(
+    content_id: vector<u8>,
+    pkg_version: &PackageVersion,
+    capability: &AccessCapability,
+    service: &ServiceProvider,
+    clock: &Clock,
+) {
+    seal_approve(content_id, pkg_version, capability, service, clock)
+}
+
 #[test_only]
 public fun create_for_testing(ctx: &mut TxContext): (PackageVersion, PackageVersionCap) {
     let pkg_version = PackageVersion {
@@ -16,6 +16,7 @@ use apex_protocol::apex_payments::{
     ProtocolConfig,
     ServiceProvider,
     AccessCapability,
+    BundleCapability,
     PaymentStream,
     AgentWallet,
     ShieldSession,
@@ -30,6 +31,10 @@ use apex_protocol::apex_trading::{
     TradingService,
 };
 
+use apex_protocol::apex_fund::{Self, HedgeFund, InvestorPosition, TraderCap};
+
+use apex_protocol::apex_seal::{Self, PackageVersion};
+
 // ==================== Test Addresses ====================
 const ADMIN: address = @0xAD;
 const PROVIDER: address = @0x1;
@@ -41,6 +46,7 @@ const OWNER: address = @0x5;
 // ==================== Test Constants ====================
 const MIST_PER_SUI: u64 = 1_000_000_000;
 const REGISTRATION_FEE: u64 = 100_000_000; // 0.1 SUI
+const U64_MAX: u64 = 18446744073709551615;
 
 // ==================== Helper Functions ====================
 
@@ -84,6 +90,113 @@ fun test_protocol_initialization() {
     ts::end(scenario);
 }
 
+#[test]
+fun test_initialize_protocol_shares_config_and_gives_sender_admin_cap() {
+    // The entry point the demo calls, rather than the test-only init
+    let mut scenario = ts::begin(PROVIDER);
+    apex_payments::initialize_protocol(ts::ctx(&mut scenario));
+
+    ts::next_tx(&mut scenario, PROVIDER);
+    {
+        assert!(ts::has_most_recent_shared<ProtocolConfig>(), 0);
+        assert!(!ts::has_most_recent_for_address<ProtocolConfig>(PROVIDER), 1);
+        assert!(ts::has_most_recent_for_sender<AdminCap>(&scenario), 2);
+        assert!(!ts::has_most_recent_for_address<AdminCap>(ADMIN), 3);
+    };
+
+    ts::end(scenario);
+}
+
+// ==================== Admin Privilege Tests ====================
+// Admin functions take `&AdminCap`, so the type system already rules out
+// calling them without one, and the cap cannot be constructed outside
+// apex_payments. What is left to check is who actually holds it.
+
+#[test]
+fun test_admin_cap_holder_runs_admin_actions() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    ts::next_tx(&mut scenario, ADMIN);
+    {
+        let admin_cap = ts::take_from_sender<AdminCap>(&scenario);
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+
+        apex_payments::set_protocol_fee(&admin_cap, &mut config, 100);
+        assert!(apex_payments::protocol_fee_bps(&config) == 100, 0);
+
+        apex_payments::create_registry(&admin_cap, ts::ctx(&mut scenario));
+        let meter = apex_payments::register_meter(
+            &admin_cap,
+            x"0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef",
+            b"pcr0:abc123",
+            b"Admin Meter",
+            ts::ctx(&mut scenario)
+        );
+        assert!(apex_payments::meter_is_active(&meter), 1);
+
+        transfer::public_transfer(meter, ADMIN);
+        ts::return_shared(config);
+        ts::return_to_sender(&scenario, admin_cap);
+    };
+
+    ts::next_tx(&mut scenario, ADMIN);
+    {
+        assert!(ts::has_most_recent_shared<ServiceRegistry>(), 2);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+#[expected_failure(abort_code = ts::EEmptyInventory)]
+fun test_non_admin_holds_no_admin_cap() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    // Only init's sender receives the cap; anyone else comes up empty
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let admin_cap = ts::take_from_sender<AdminCap>(&scenario);
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        apex_payments::set_protocol_fee(&admin_cap, &mut config, 1000);
+        ts::return_shared(config);
+        ts::return_to_sender(&scenario, admin_cap);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+fun test_transferred_admin_cap_moves_privilege() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    ts::next_tx(&mut scenario, ADMIN);
+    {
+        let admin_cap = ts::take_from_sender<AdminCap>(&scenario);
+        transfer::public_transfer(admin_cap, OWNER);
+    };
+
+    // The new holder can act; the old one no longer has a cap to act with
+    ts::next_tx(&mut scenario, OWNER);
+    {
+        let admin_cap = ts::take_from_sender<AdminCap>(&scenario);
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        apex_payments::set_protocol_paused(&admin_cap, &mut config, true);
+        assert!(apex_payments::protocol_is_paused(&config), 0);
+        ts::return_shared(config);
+        ts::return_to_sender(&scenario, admin_cap);
+    };
+
+    ts::next_tx(&mut scenario, ADMIN);
+    {
+        assert!(!ts::has_most_recent_for_sender<AdminCap>(&scenario), 1);
+    };
+
+    ts::end(scenario);
+}
+
 // ==================== Service Registration Tests ====================
 
 #[test]
@@ -210,18 +323,17 @@ fun test_purchase_access() {
 }
 
 #[test]
-fun test_use_access() {
+fun test_purchase_keeps_config_and_service_shared() {
     let mut scenario = ts::begin(ADMIN);
     setup_protocol(&mut scenario);
 
-    // Setup: register service and purchase access
     ts::next_tx(&mut scenario, PROVIDER);
     {
         let mut config = ts::take_shared<ProtocolConfig>(&scenario);
         apex_payments::register_service(
             &mut config,
-            b"API",
-            b"Test",
+            b"API Service",
+            b"Test service",
             10_000_000,
             mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
             ts::ctx(&mut scenario)
@@ -230,11 +342,10 @@ fun test_use_access() {
     };
 
     ts::next_tx(&mut scenario, AGENT);
-    {
+    let (config_id, service_id) = {
         let mut config = ts::take_shared<ProtocolConfig>(&scenario);
         let mut service = ts::take_shared<ServiceProvider>(&scenario);
-        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
-        clock::set_for_testing(&mut clock, 1000);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
 
         let capability = apex_payments::purchase_access(
             &mut config,
@@ -242,60 +353,46 @@ fun test_use_access() {
             mint_sui(1 * MIST_PER_SUI, ts::ctx(&mut scenario)),
             100,
             3600_000,
-            0, // no rate limit
+            10,
             &clock,
             ts::ctx(&mut scenario)
         );
-
         transfer::public_transfer(capability, AGENT);
 
+        let ids = (object::id(&config), object::id(&service));
         clock::destroy_for_testing(clock);
         ts::return_shared(service);
         ts::return_shared(config);
+        ids
     };
 
-    // Use access
+    // Both mutated objects must still be shared (and the same objects)
     ts::next_tx(&mut scenario, AGENT);
     {
-        let service = ts::take_shared<ServiceProvider>(&scenario);
-        let mut capability = ts::take_from_sender<AccessCapability>(&scenario);
-        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
-        clock::set_for_testing(&mut clock, 2000);
-
-        // Use 5 units
-        let success = apex_payments::use_access(
-            &mut capability,
-            &service,
-            5,
-            &clock,
-            ts::ctx(&mut scenario)
-        );
-
-        assert!(success, 0);
-        assert!(apex_payments::capability_remaining(&capability) == 95, 1);
+        assert!(ts::has_most_recent_shared<ProtocolConfig>(), 0);
+        assert!(ts::has_most_recent_shared<ServiceProvider>(), 1);
 
-        clock::destroy_for_testing(clock);
-        ts::return_to_sender(&scenario, capability);
+        let config = ts::take_shared_by_id<ProtocolConfig>(&scenario, config_id);
+        let service = ts::take_shared_by_id<ServiceProvider>(&scenario, service_id);
         ts::return_shared(service);
+        ts::return_shared(config);
     };
 
     ts::end(scenario);
 }
 
 #[test]
-#[expected_failure(abort_code = apex_payments::EExpired)]
-fun test_use_expired_access() {
+fun test_purchase_access_expiry_near_u64_max() {
     let mut scenario = ts::begin(ADMIN);
     setup_protocol(&mut scenario);
 
-    // Setup: register service
     ts::next_tx(&mut scenario, PROVIDER);
     {
         let mut config = ts::take_shared<ProtocolConfig>(&scenario);
         apex_payments::register_service(
             &mut config,
-            b"API",
-            b"Test",
+            b"API Service",
+            b"Test service",
             10_000_000,
             mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
             ts::ctx(&mut scenario)
@@ -303,177 +400,112 @@ fun test_use_expired_access() {
         ts::return_shared(config);
     };
 
-    // Purchase access with 1 hour duration
+    // Clock starts exactly `duration` before u64::MAX
     ts::next_tx(&mut scenario, AGENT);
     {
         let mut config = ts::take_shared<ProtocolConfig>(&scenario);
         let mut service = ts::take_shared<ServiceProvider>(&scenario);
         let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
-        clock::set_for_testing(&mut clock, 1000);
+        let duration = 3600_000;
+        clock::set_for_testing(&mut clock, U64_MAX - duration);
 
         let capability = apex_payments::purchase_access(
             &mut config,
             &mut service,
             mint_sui(1 * MIST_PER_SUI, ts::ctx(&mut scenario)),
             100,
-            3600_000, // 1 hour
-            0,
+            duration,
+            10,
             &clock,
             ts::ctx(&mut scenario)
         );
+        assert!(apex_payments::capability_expires_at(&capability) == U64_MAX, 0);
 
         transfer::public_transfer(capability, AGENT);
-
         clock::destroy_for_testing(clock);
         ts::return_shared(service);
         ts::return_shared(config);
     };
 
-    // Try to use after expiration
-    ts::next_tx(&mut scenario, AGENT);
-    {
-        let service = ts::take_shared<ServiceProvider>(&scenario);
-        let mut capability = ts::take_from_sender<AccessCapability>(&scenario);
-        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
-        clock::set_for_testing(&mut clock, 1000 + 3600_001); // Just past expiry
-
-        // This should fail - capability expired
-        apex_payments::use_access(
-            &mut capability,
-            &service,
-            1,
-            &clock,
-            ts::ctx(&mut scenario)
-        );
-
-        clock::destroy_for_testing(clock);
-        ts::return_to_sender(&scenario, capability);
-        ts::return_shared(service);
-    };
-
     ts::end(scenario);
 }
 
-// ==================== Streaming Payment Tests ====================
-
 #[test]
-fun test_open_and_consume_stream() {
+fun test_purchase_cost_is_exactly_price_times_units() {
     let mut scenario = ts::begin(ADMIN);
     setup_protocol(&mut scenario);
 
-    // Register service
     ts::next_tx(&mut scenario, PROVIDER);
     {
         let mut config = ts::take_shared<ProtocolConfig>(&scenario);
         apex_payments::register_service(
             &mut config,
-            b"Streaming API",
-            b"Pay per second service",
-            1_000_000, // 0.001 SUI per unit
+            b"API Service",
+            b"Test service",
+            10_000_000, // 0.01 SUI per unit
             mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
             ts::ctx(&mut scenario)
         );
         ts::return_shared(config);
     };
 
-    // Open stream
+    // Overpay so the refund coin shows exactly how much was taken
+    let payment_amount = 1_500_000_000;
+    let expected_cost = 1_000_000_000; // 10_000_000 * 100
     ts::next_tx(&mut scenario, AGENT);
-    {
-        let config = ts::take_shared<ProtocolConfig>(&scenario);
-        let service = ts::take_shared<ServiceProvider>(&scenario);
-        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
-        clock::set_for_testing(&mut clock, 1000);
-
-        let escrow = mint_sui(10 * MIST_PER_SUI, ts::ctx(&mut scenario)); // 10 SUI
-
-        apex_payments::open_stream(
-            &config,
-            &service,
-            escrow,
-            10000, // max 10000 units
-            &clock,
-            ts::ctx(&mut scenario)
-        );
-
-        clock::destroy_for_testing(clock);
-        ts::return_shared(service);
-        ts::return_shared(config);
-    };
-
-    // Provider records consumption
-    ts::next_tx(&mut scenario, PROVIDER);
-    {
-        let mut stream = ts::take_shared<PaymentStream>(&scenario);
+    let treasury_before = {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
         let mut service = ts::take_shared<ServiceProvider>(&scenario);
-        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
-        clock::set_for_testing(&mut clock, 5000);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        let treasury_before = apex_payments::treasury_balance(&config);
 
-        apex_payments::record_stream_consumption(
-            &mut stream,
+        let capability = apex_payments::purchase_access(
+            &mut config,
             &mut service,
-            100, // 100 units consumed
+            mint_sui(payment_amount, ts::ctx(&mut scenario)),
+            100,
+            3600_000,
+            10,
             &clock,
             ts::ctx(&mut scenario)
         );
+        assert!(apex_payments::capability_remaining(&capability) == 100, 0);
 
-        assert!(apex_payments::stream_consumed(&stream) == 100, 0);
-
+        transfer::public_transfer(capability, AGENT);
         clock::destroy_for_testing(clock);
         ts::return_shared(service);
-        ts::return_shared(stream);
+        ts::return_shared(config);
+        treasury_before
     };
 
-    ts::end(scenario);
-}
-
-// ==================== Agent Wallet Tests ====================
-
-#[test]
-fun test_create_agent_wallet() {
-    let mut scenario = ts::begin(ADMIN);
-    setup_protocol(&mut scenario);
-
-    // Create agent wallet
+    // Refund + protocol fee + provider revenue account for every MIST
     ts::next_tx(&mut scenario, AGENT);
     {
         let config = ts::take_shared<ProtocolConfig>(&scenario);
-        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
-        clock::set_for_testing(&mut clock, 1000);
-
-        let funding = mint_sui(5 * MIST_PER_SUI, ts::ctx(&mut scenario));
+        let service = ts::take_shared<ServiceProvider>(&scenario);
+        let refund = ts::take_from_sender<Coin<SUI>>(&scenario);
 
-        apex_payments::create_agent_wallet(
-            &config,
-            b"bot-001",
-            100_000_000, // 0.1 SUI spend limit
-            1_000_000_000, // 1 SUI daily limit
-            false, // restrict_funding
-            funding,
-            &clock,
-            ts::ctx(&mut scenario)
-        );
+        let fee = apex_payments::treasury_balance(&config) - treasury_before;
+        let revenue = apex_payments::service_revenue(&service);
+        assert!(coin::value(&refund) == payment_amount - expected_cost, 1);
+        assert!(fee == 5_000_000, 2); // 0.5% of cost, taken out of the cost
+        assert!(revenue == expected_cost - fee, 3);
+        assert!(fee + revenue + coin::value(&refund) == payment_amount, 4);
 
-        clock::destroy_for_testing(clock);
+        ts::return_to_sender(&scenario, refund);
+        ts::return_shared(service);
         ts::return_shared(config);
     };
 
-    // Verify wallet
-    ts::next_tx(&mut scenario, AGENT);
-    {
-        let wallet = ts::take_from_sender<AgentWallet>(&scenario);
-        assert!(apex_payments::agent_wallet_balance(&wallet) == 5 * MIST_PER_SUI, 0);
-        ts::return_to_sender(&scenario, wallet);
-    };
-
     ts::end(scenario);
 }
 
 #[test]
-fun test_agent_wallet_spending_limits() {
+fun test_use_access() {
     let mut scenario = ts::begin(ADMIN);
     setup_protocol(&mut scenario);
 
-    // Register service
+    // Setup: register service and purchase access
     ts::next_tx(&mut scenario, PROVIDER);
     {
         let mut config = ts::take_shared<ProtocolConfig>(&scenario);
@@ -481,78 +513,73 @@ fun test_agent_wallet_spending_limits() {
             &mut config,
             b"API",
             b"Test",
-            10_000_000, // 0.01 SUI per unit
+            10_000_000,
             mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
             ts::ctx(&mut scenario)
         );
         ts::return_shared(config);
     };
 
-    // Create agent wallet
     ts::next_tx(&mut scenario, AGENT);
     {
-        let config = ts::take_shared<ProtocolConfig>(&scenario);
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        let mut service = ts::take_shared<ServiceProvider>(&scenario);
         let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
         clock::set_for_testing(&mut clock, 1000);
 
-        apex_payments::create_agent_wallet(
-            &config,
-            b"bot-001",
-            100_000_000, // 0.1 SUI spend limit per tx
-            1_000_000_000, // 1 SUI daily limit
-            false, // restrict_funding
-            mint_sui(5 * MIST_PER_SUI, ts::ctx(&mut scenario)),
-            &clock,
-            ts::ctx(&mut scenario)
-        );
-
+        let capability = apex_payments::purchase_access(
+            &mut config,
+            &mut service,
+            mint_sui(1 * MIST_PER_SUI, ts::ctx(&mut scenario)),
+            100,
+            3600_000,
+            0, // no rate limit
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        transfer::public_transfer(capability, AGENT);
+
         clock::destroy_for_testing(clock);
+        ts::return_shared(service);
         ts::return_shared(config);
     };
 
-    // Agent purchases access using wallet
+    // Use access
     ts::next_tx(&mut scenario, AGENT);
     {
-        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
-        let mut service = ts::take_shared<ServiceProvider>(&scenario);
-        let mut wallet = ts::take_from_sender<AgentWallet>(&scenario);
+        let service = ts::take_shared<ServiceProvider>(&scenario);
+        let mut capability = ts::take_from_sender<AccessCapability>(&scenario);
         let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
         clock::set_for_testing(&mut clock, 2000);
 
-        let capability = apex_payments::agent_purchase_access(
-            &mut wallet,
-            &mut config,
-            &mut service,
-            5, // 5 units = 0.05 SUI (under 0.1 limit)
-            3600_000,
-            0,
+        // Use 5 units
+        let success = apex_payments::use_access(
+            &mut capability,
+            &service,
+            5,
             &clock,
             ts::ctx(&mut scenario)
         );
 
-        // Verify purchase worked
-        assert!(apex_payments::capability_remaining(&capability) == 5, 0);
-
-        // Check wallet balance decreased
-        assert!(apex_payments::agent_wallet_balance(&wallet) == 5 * MIST_PER_SUI - 50_000_000, 1);
+        assert!(success, 0);
+        assert!(apex_payments::capability_remaining(&capability) == 95, 1);
 
-        transfer::public_transfer(capability, AGENT);
         clock::destroy_for_testing(clock);
-        ts::return_to_sender(&scenario, wallet);
+        ts::return_to_sender(&scenario, capability);
         ts::return_shared(service);
-        ts::return_shared(config);
     };
 
     ts::end(scenario);
 }
 
+/// A capability used down to zero units stays in storage; only
+/// burn_capability deletes it
 #[test]
-#[expected_failure(abort_code = apex_payments::EExceededLimit)]
-fun test_agent_wallet_exceeds_spend_limit() {
+fun test_exhausted_capability_deleted_only_when_burned() {
     let mut scenario = ts::begin(ADMIN);
     setup_protocol(&mut scenario);
 
-    // Register service
     ts::next_tx(&mut scenario, PROVIDER);
     {
         let mut config = ts::take_shared<ProtocolConfig>(&scenario);
@@ -567,349 +594,463 @@ fun test_agent_wallet_exceeds_spend_limit() {
         ts::return_shared(config);
     };
 
-    // Create agent wallet with low spend limit
-    ts::next_tx(&mut scenario, AGENT);
-    {
-        let config = ts::take_shared<ProtocolConfig>(&scenario);
-        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
-        clock::set_for_testing(&mut clock, 1000);
-
-        apex_payments::create_agent_wallet(
-            &config,
-            b"bot-001",
-            50_000_000, // Only 0.05 SUI spend limit
-            1_000_000_000,
-            false, // restrict_funding
-            mint_sui(5 * MIST_PER_SUI, ts::ctx(&mut scenario)),
-            &clock,
-            ts::ctx(&mut scenario)
-        );
-
-        clock::destroy_for_testing(clock);
-        ts::return_shared(config);
-    };
-
-    // Try to purchase more than spend limit
     ts::next_tx(&mut scenario, AGENT);
-    {
+    let cap_id = {
         let mut config = ts::take_shared<ProtocolConfig>(&scenario);
         let mut service = ts::take_shared<ServiceProvider>(&scenario);
-        let mut wallet = ts::take_from_sender<AgentWallet>(&scenario);
-        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
-        clock::set_for_testing(&mut clock, 2000);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
 
-        // Try to buy 10 units = 0.1 SUI (exceeds 0.05 limit)
-        let capability = apex_payments::agent_purchase_access(
-            &mut wallet,
+        let mut capability = apex_payments::purchase_access(
             &mut config,
             &mut service,
-            10, // 0.1 SUI - exceeds limit!
-            3600_000,
+            mint_sui(20_000_000, ts::ctx(&mut scenario)),
+            2,
+            0,
             0,
             &clock,
             ts::ctx(&mut scenario)
         );
+        apex_payments::use_access(&mut capability, &service, 2, &clock, ts::ctx(&mut scenario));
+        assert!(apex_payments::capability_remaining(&capability) == 0, 0);
 
+        let cap_id = object::id(&capability);
         transfer::public_transfer(capability, AGENT);
         clock::destroy_for_testing(clock);
-        ts::return_to_sender(&scenario, wallet);
         ts::return_shared(service);
         ts::return_shared(config);
+        cap_id
+    };
+
+    let effects = ts::next_tx(&mut scenario, AGENT);
+    assert!(!ts::deleted(&effects).contains(&cap_id), 1);
+    {
+        let capability = ts::take_from_sender_by_id<AccessCapability>(&scenario, cap_id);
+        apex_payments::burn_capability(capability);
     };
 
+    let effects = ts::next_tx(&mut scenario, AGENT);
+    assert!(ts::deleted(&effects).contains(&cap_id), 2);
+
     ts::end(scenario);
 }
 
-// ==================== Shield Transfer Tests ====================
-
+/// A rate-limited, non-expiring capability keeps working across epoch
+/// boundaries; the per-epoch allowance resets instead of carrying over
 #[test]
-fun test_shield_transfer_complete() {
+fun test_long_lived_capability_survives_epoch_advance() {
     let mut scenario = ts::begin(ADMIN);
     setup_protocol(&mut scenario);
 
-    // Sender initiates shield transfer
-    ts::next_tx(&mut scenario, AGENT);
+    ts::next_tx(&mut scenario, PROVIDER);
     {
-        let config = ts::take_shared<ProtocolConfig>(&scenario);
-        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
-        clock::set_for_testing(&mut clock, 1000);
-
-        let payment = mint_sui(100 * MIST_PER_SUI, ts::ctx(&mut scenario));
-
-        // Hash of "secret-passphrase"
-        let secret_hash = sui::hash::keccak256(&b"secret-passphrase");
-
-        apex_payments::initiate_shield_transfer(
-            &config,
-            RECIPIENT,
-            payment,
-            86400_000, // 24 hours
-            secret_hash,
-            true, // require_secret
-            &clock,
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        apex_payments::register_service(
+            &mut config,
+            b"API",
+            b"Test",
+            10_000_000,
+            mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
             ts::ctx(&mut scenario)
         );
-
-        clock::destroy_for_testing(clock);
         ts::return_shared(config);
     };
 
-    // Recipient claims with correct secret
-    ts::next_tx(&mut scenario, RECIPIENT);
+    ts::next_tx(&mut scenario, AGENT);
     {
-        let session = ts::take_shared<ShieldSession>(&scenario);
-        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
-        clock::set_for_testing(&mut clock, 5000);
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        let mut service = ts::take_shared<ServiceProvider>(&scenario);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
 
-        apex_payments::complete_shield_transfer(
-            session,
-            b"secret-passphrase",
+        let mut capability = apex_payments::purchase_access(
+            &mut config,
+            &mut service,
+            mint_sui(1 * MIST_PER_SUI, ts::ctx(&mut scenario)),
+            100,
+            0, // no expiry
+            5, // 5 units per epoch
             &clock,
             ts::ctx(&mut scenario)
         );
+        // Use the whole allowance for this epoch
+        apex_payments::use_access(&mut capability, &service, 5, &clock, ts::ctx(&mut scenario));
 
+        transfer::public_transfer(capability, AGENT);
         clock::destroy_for_testing(clock);
+        ts::return_shared(service);
+        ts::return_shared(config);
     };
 
-    // Verify recipient received funds
-    ts::next_tx(&mut scenario, RECIPIENT);
+    ts::next_epoch(&mut scenario, AGENT);
+    ts::next_epoch(&mut scenario, AGENT);
+    ts::next_epoch(&mut scenario, AGENT);
     {
-        let coin = ts::take_from_sender<Coin<SUI>>(&scenario);
-        assert!(coin::value(&coin) == 100 * MIST_PER_SUI, 0);
-        ts::return_to_sender(&scenario, coin);
+        let service = ts::take_shared<ServiceProvider>(&scenario);
+        let mut capability = ts::take_from_sender<AccessCapability>(&scenario);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        assert!(ts::ctx(&mut scenario).epoch() == 3, 0);
+
+        let success = apex_payments::use_access(&mut capability, &service, 5, &clock, ts::ctx(&mut scenario));
+        assert!(success, 1);
+        assert!(apex_payments::capability_remaining(&capability) == 90, 2);
+
+        clock::destroy_for_testing(clock);
+        ts::return_to_sender(&scenario, capability);
+        ts::return_shared(service);
     };
 
     ts::end(scenario);
 }
 
-#[test]
-#[expected_failure(abort_code = apex_payments::EInvalidSecret)]
-fun test_shield_transfer_wrong_secret() {
-    let mut scenario = ts::begin(ADMIN);
-    setup_protocol(&mut scenario);
-
-    // Sender initiates
-    ts::next_tx(&mut scenario, AGENT);
+/// Register a service available from 1_000ms to 5_000ms and have AGENT
+/// buy one unit at `now_ms`
+fun purchase_windowed_service_at(scenario: &mut Scenario, now_ms: u64) {
+    ts::next_tx(scenario, PROVIDER);
     {
-        let config = ts::take_shared<ProtocolConfig>(&scenario);
-        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
-        clock::set_for_testing(&mut clock, 1000);
+        let mut config = ts::take_shared<ProtocolConfig>(scenario);
+        apex_payments::register_service_with_availability(
+            &mut config,
+            b"Market Hours API",
+            b"Only sold during the window",
+            10_000_000,
+            1_000,
+            5_000,
+            mint_sui(REGISTRATION_FEE, ts::ctx(scenario)),
+            ts::ctx(scenario)
+        );
+        ts::return_shared(config);
+    };
 
-        let payment = mint_sui(100 * MIST_PER_SUI, ts::ctx(&mut scenario));
-        let secret_hash = sui::hash::keccak256(&b"correct-secret");
+    ts::next_tx(scenario, AGENT);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(scenario);
+        let mut service = ts::take_shared<ServiceProvider>(scenario);
+        let mut clock = clock::create_for_testing(ts::ctx(scenario));
+        clock::set_for_testing(&mut clock, now_ms);
 
-        apex_payments::initiate_shield_transfer(
-            &config,
-            RECIPIENT,
-            payment,
-            86400_000,
-            secret_hash,
-            true, // require_secret
+        let capability = apex_payments::purchase_access(
+            &mut config,
+            &mut service,
+            mint_sui(10_000_000, ts::ctx(scenario)),
+            1,
+            0,
+            0,
             &clock,
-            ts::ctx(&mut scenario)
+            ts::ctx(scenario)
         );
+        transfer::public_transfer(capability, AGENT);
 
         clock::destroy_for_testing(clock);
+        ts::return_shared(service);
         ts::return_shared(config);
     };
+}
 
-    // Try to claim with wrong secret
-    ts::next_tx(&mut scenario, RECIPIENT);
-    {
-        let session = ts::take_shared<ShieldSession>(&scenario);
-        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
-        clock::set_for_testing(&mut clock, 5000);
+#[test]
+#[expected_failure(abort_code = apex_payments::EServiceUnavailable)]
+fun test_purchase_before_availability_window() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+    purchase_windowed_service_at(&mut scenario, 500);
+    ts::end(scenario);
+}
 
-        apex_payments::complete_shield_transfer(
-            session,
-            b"wrong-secret", // Wrong!
-            &clock,
-            ts::ctx(&mut scenario)
-        );
+#[test]
+fun test_purchase_within_availability_window() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+    purchase_windowed_service_at(&mut scenario, 2_000);
 
-        clock::destroy_for_testing(clock);
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        assert!(ts::has_most_recent_for_sender<AccessCapability>(&scenario), 0);
+        let service = ts::take_shared<ServiceProvider>(&scenario);
+        let (from, until) = apex_payments::service_availability(&service);
+        assert!(from == 1_000 && until == 5_000, 1);
+        ts::return_shared(service);
     };
 
     ts::end(scenario);
 }
 
-// ==================== Trading Intent Tests ====================
+#[test]
+#[expected_failure(abort_code = apex_payments::EServiceUnavailable)]
+fun test_purchase_after_availability_window() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+    purchase_windowed_service_at(&mut scenario, 6_000);
+    ts::end(scenario);
+}
 
 #[test]
-fun test_create_and_fill_intent() {
+fun test_usage_history_records_each_use() {
     let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    ts::next_tx(&mut scenario, PROVIDER);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        apex_payments::register_service(
+            &mut config,
+            b"API",
+            b"Test",
+            10_000_000,
+            mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
+            ts::ctx(&mut scenario)
+        );
+        ts::return_shared(config);
+    };
 
-    // Agent creates swap intent
     ts::next_tx(&mut scenario, AGENT);
     {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        let mut service = ts::take_shared<ServiceProvider>(&scenario);
         let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
         clock::set_for_testing(&mut clock, 1000);
 
-        let input_coin = mint_sui(1 * MIST_PER_SUI, ts::ctx(&mut scenario));
-
-        apex_trading::create_swap_intent<SUI>(
-            input_coin,
-            2_000_000, // min 2 USDC output (simulated)
-            AGENT,
-            3600_000, // 1 hour
+        let mut capability = apex_payments::purchase_access(
+            &mut config,
+            &mut service,
+            mint_sui(1 * MIST_PER_SUI, ts::ctx(&mut scenario)),
+            100,
+            3600_000,
+            0,
             &clock,
             ts::ctx(&mut scenario)
         );
+        assert!(!apex_payments::capability_tracks_usage(&capability), 0);
+        apex_payments::enable_usage_history(&mut capability);
+
+        transfer::public_transfer(capability, AGENT);
 
         clock::destroy_for_testing(clock);
+        ts::return_shared(service);
+        ts::return_shared(config);
     };
 
-    // Verify intent was created
-    ts::next_tx(&mut scenario, EXECUTOR);
+    // Consume units twice at different times
+    ts::next_tx(&mut scenario, AGENT);
     {
-        let intent = ts::take_shared<SwapIntent<SUI>>(&scenario);
-        assert!(apex_trading::intent_escrowed_amount(&intent) == 1 * MIST_PER_SUI, 0);
-        assert!(apex_trading::intent_min_output(&intent) == 2_000_000, 1);
-        assert!(!apex_trading::intent_is_filled(&intent), 2);
-        ts::return_shared(intent);
-    };
-
-    // Executor fills intent
-    ts::next_tx(&mut scenario, EXECUTOR);
-    {
-        let mut intent = ts::take_shared<SwapIntent<SUI>>(&scenario);
+        let service = ts::take_shared<ServiceProvider>(&scenario);
+        let mut capability = ts::take_from_sender<AccessCapability>(&scenario);
         let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
-        clock::set_for_testing(&mut clock, 2000);
 
-        // Executor provides output (simulating DeepBook swap result)
-        let output_coin = mint_sui(3_000_000, ts::ctx(&mut scenario)); // More than min output
+        clock::set_for_testing(&mut clock, 2000);
+        apex_payments::use_access(&mut capability, &service, 5, &clock, ts::ctx(&mut scenario));
 
-        let (escrowed_input, receipt) = apex_trading::fill_intent<SUI, SUI>(
-            &mut intent,
-            output_coin,
-            &clock,
-            ts::ctx(&mut scenario)
-        );
+        clock::set_for_testing(&mut clock, 3500);
+        apex_payments::use_access(&mut capability, &service, 7, &clock, ts::ctx(&mut scenario));
 
-        // Verify executor received escrowed input
-        assert!(coin::value(&escrowed_input) == 1 * MIST_PER_SUI, 0);
+        assert!(apex_payments::usage_history_length(&capability) == 2, 1);
+        let (ts0, units0) = apex_payments::usage_history_entry(&capability, 0);
+        assert!(ts0 == 2000 && units0 == 5, 2);
+        let (ts1, units1) = apex_payments::usage_history_entry(&capability, 1);
+        assert!(ts1 == 3500 && units1 == 7, 3);
+        assert!(apex_payments::capability_remaining(&capability) == 88, 4);
 
-        // Verify intent marked as filled
-        assert!(apex_trading::intent_is_filled(&intent), 1);
+        // Burning removes the history field along with the capability
+        apex_payments::burn_capability(capability);
 
-        transfer::public_transfer(escrowed_input, EXECUTOR);
-        transfer::public_transfer(receipt, EXECUTOR);
         clock::destroy_for_testing(clock);
-        ts::return_shared(intent);
+        ts::return_shared(service);
     };
 
     ts::end(scenario);
 }
 
 #[test]
-#[expected_failure(abort_code = apex_trading::EInsufficientOutput)]
-fun test_fill_intent_insufficient_output() {
+#[expected_failure(abort_code = apex_payments::EExpired)]
+fun test_is_expired_agrees_with_use_access() {
     let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    ts::next_tx(&mut scenario, PROVIDER);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        apex_payments::register_service(
+            &mut config,
+            b"API",
+            b"Test",
+            10_000_000,
+            mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
+            ts::ctx(&mut scenario)
+        );
+        ts::return_shared(config);
+    };
 
-    // Agent creates intent
     ts::next_tx(&mut scenario, AGENT);
     {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        let mut service = ts::take_shared<ServiceProvider>(&scenario);
         let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
         clock::set_for_testing(&mut clock, 1000);
 
-        apex_trading::create_swap_intent<SUI>(
+        let mut capability = apex_payments::purchase_access(
+            &mut config,
+            &mut service,
             mint_sui(1 * MIST_PER_SUI, ts::ctx(&mut scenario)),
-            2_000_000, // min 2 million mist
-            AGENT,
-            3600_000,
+            100,
+            3600_000, // 1 hour
+            0,
             &clock,
             ts::ctx(&mut scenario)
         );
 
+        // Fresh, and still valid at the exact expiry timestamp
+        assert!(!apex_payments::is_expired(&capability, &clock), 0);
+        clock::set_for_testing(&mut clock, 1000 + 3600_000);
+        assert!(!apex_payments::is_expired(&capability, &clock), 1);
+        apex_payments::use_access(&mut capability, &service, 1, &clock, ts::ctx(&mut scenario));
+
+        // One millisecond later both checks reject it
+        clock::set_for_testing(&mut clock, 1000 + 3600_001);
+        assert!(apex_payments::is_expired(&capability, &clock), 2);
+        apex_payments::use_access(&mut capability, &service, 1, &clock, ts::ctx(&mut scenario));
+
+        transfer::public_transfer(capability, AGENT);
         clock::destroy_for_testing(clock);
+        ts::return_shared(service);
+        ts::return_shared(config);
     };
 
-    // Try to fill with insufficient output
-    ts::next_tx(&mut scenario, EXECUTOR);
+    ts::end(scenario);
+}
+
+/// Service with `grace_ms`, plus a 100-unit capability bought at t=1000
+/// that expires one hour later, held by AGENT
+fun setup_grace_capability(scenario: &mut Scenario, grace_ms: u64) {
+    setup_protocol(scenario);
+
+    ts::next_tx(scenario, PROVIDER);
     {
-        let mut intent = ts::take_shared<SwapIntent<SUI>>(&scenario);
-        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
-        clock::set_for_testing(&mut clock, 2000);
+        let mut config = ts::take_shared<ProtocolConfig>(scenario);
+        apex_payments::register_service(
+            &mut config,
+            b"API",
+            b"Latency-sensitive endpoint",
+            10_000_000,
+            mint_sui(REGISTRATION_FEE, ts::ctx(scenario)),
+            ts::ctx(scenario)
+        );
+        ts::return_shared(config);
+    };
 
-        // Only 1 million - below 2 million minimum
-        let insufficient_output = mint_sui(1_000_000, ts::ctx(&mut scenario));
+    ts::next_tx(scenario, PROVIDER);
+    {
+        let mut service = ts::take_shared<ServiceProvider>(scenario);
+        apex_payments::set_service_grace_ms(&mut service, grace_ms, ts::ctx(scenario));
+        ts::return_shared(service);
+    };
 
-        let (input, receipt) = apex_trading::fill_intent<SUI, SUI>(
-            &mut intent,
-            insufficient_output,
+    ts::next_tx(scenario, AGENT);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(scenario);
+        let mut service = ts::take_shared<ServiceProvider>(scenario);
+        let mut clock = clock::create_for_testing(ts::ctx(scenario));
+        clock::set_for_testing(&mut clock, 1000);
+
+        let capability = apex_payments::purchase_access(
+            &mut config,
+            &mut service,
+            mint_sui(1 * MIST_PER_SUI, ts::ctx(scenario)),
+            100,
+            3600_000,
+            0,
             &clock,
-            ts::ctx(&mut scenario)
+            ts::ctx(scenario)
         );
 
-        transfer::public_transfer(input, EXECUTOR);
-        transfer::public_transfer(receipt, EXECUTOR);
+        transfer::public_transfer(capability, AGENT);
         clock::destroy_for_testing(clock);
-        ts::return_shared(intent);
+        ts::return_shared(service);
+        ts::return_shared(config);
     };
-
-    ts::end(scenario);
 }
 
 #[test]
-fun test_cancel_intent() {
+fun test_use_access_within_grace_period() {
     let mut scenario = ts::begin(ADMIN);
+    setup_grace_capability(&mut scenario, 60_000);
 
-    // Agent creates intent
     ts::next_tx(&mut scenario, AGENT);
     {
+        let service = ts::take_shared<ServiceProvider>(&scenario);
+        let mut capability = ts::take_from_sender<AccessCapability>(&scenario);
         let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
-        clock::set_for_testing(&mut clock, 1000);
+        assert!(apex_payments::service_grace_ms(&service) == 60_000, 0);
 
-        apex_trading::create_swap_intent<SUI>(
-            mint_sui(1 * MIST_PER_SUI, ts::ctx(&mut scenario)),
-            2_000_000,
-            AGENT,
-            3600_000,
-            &clock,
-            ts::ctx(&mut scenario)
-        );
+        // 30s past nominal expiry: expired, but still inside the grace window
+        clock::set_for_testing(&mut clock, 1000 + 3600_000 + 30_000);
+        assert!(apex_payments::is_expired(&capability, &clock), 1);
+        apex_payments::use_access(&mut capability, &service, 5, &clock, ts::ctx(&mut scenario));
+        assert!(apex_payments::capability_remaining(&capability) == 95, 2);
 
+        // The last millisecond of grace is still accepted
+        clock::set_for_testing(&mut clock, 1000 + 3600_000 + 60_000);
+        apex_payments::use_access(&mut capability, &service, 5, &clock, ts::ctx(&mut scenario));
+        assert!(apex_payments::capability_remaining(&capability) == 90, 3);
+
+        ts::return_to_sender(&scenario, capability);
         clock::destroy_for_testing(clock);
+        ts::return_shared(service);
     };
 
-    // Agent cancels intent
+    ts::end(scenario);
+}
+
+#[test]
+#[expected_failure(abort_code = apex_payments::EExpired)]
+fun test_use_access_past_grace_period() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_grace_capability(&mut scenario, 60_000);
+
     ts::next_tx(&mut scenario, AGENT);
     {
-        let intent = ts::take_shared<SwapIntent<SUI>>(&scenario);
+        let service = ts::take_shared<ServiceProvider>(&scenario);
+        let mut capability = ts::take_from_sender<AccessCapability>(&scenario);
         let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
-        clock::set_for_testing(&mut clock, 2000);
-
-        let refund = apex_trading::cancel_intent(
-            intent,
-            &clock,
-            ts::ctx(&mut scenario)
-        );
 
-        // Verify full refund
-        assert!(coin::value(&refund) == 1 * MIST_PER_SUI, 0);
+        clock::set_for_testing(&mut clock, 1000 + 3600_000 + 60_001);
+        apex_payments::use_access(&mut capability, &service, 1, &clock, ts::ctx(&mut scenario));
 
-        transfer::public_transfer(refund, AGENT);
+        ts::return_to_sender(&scenario, capability);
         clock::destroy_for_testing(clock);
+        ts::return_shared(service);
     };
 
     ts::end(scenario);
 }
 
-// ==================== Gated Trading Tests ====================
+#[test]
+#[expected_failure(abort_code = apex_payments::EUnauthorized)]
+fun test_set_grace_ms_provider_only() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_grace_capability(&mut scenario, 0);
+
+    // The capability holder can't grant itself extra time
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let mut service = ts::take_shared<ServiceProvider>(&scenario);
+        apex_payments::set_service_grace_ms(&mut service, 60_000, ts::ctx(&mut scenario));
+        ts::return_shared(service);
+    };
+
+    ts::end(scenario);
+}
 
 #[test]
-fun test_gated_trading_service() {
+#[expected_failure(abort_code = apex_payments::EExpired)]
+fun test_use_expired_access() {
     let mut scenario = ts::begin(ADMIN);
     setup_protocol(&mut scenario);
 
-    // Register APEX service
+    // Setup: register service
     ts::next_tx(&mut scenario, PROVIDER);
     {
         let mut config = ts::take_shared<ProtocolConfig>(&scenario);
         apex_payments::register_service(
             &mut config,
-            b"Trading API",
-            b"Premium trading signals",
+            b"API",
+            b"Test",
             10_000_000,
             mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
             ts::ctx(&mut scenario)
@@ -917,22 +1058,7 @@ fun test_gated_trading_service() {
         ts::return_shared(config);
     };
 
-    // Get service ID and create trading service
-    ts::next_tx(&mut scenario, PROVIDER);
-    {
-        let service = ts::take_shared<ServiceProvider>(&scenario);
-        let apex_service_id = object::id(&service);
-
-        apex_trading::create_and_share_trading_service(
-            apex_service_id,
-            5_000_000, // 0.005 SUI fee per trade
-            ts::ctx(&mut scenario)
-        );
-
-        ts::return_shared(service);
-    };
-
-    // Agent purchases access
+    // Purchase access with 1 hour duration
     ts::next_tx(&mut scenario, AGENT);
     {
         let mut config = ts::take_shared<ProtocolConfig>(&scenario);
@@ -945,7 +1071,7 @@ fun test_gated_trading_service() {
             &mut service,
             mint_sui(1 * MIST_PER_SUI, ts::ctx(&mut scenario)),
             100,
-            3600_000,
+            3600_000, // 1 hour
             0,
             &clock,
             ts::ctx(&mut scenario)
@@ -958,264 +1084,247 @@ fun test_gated_trading_service() {
         ts::return_shared(config);
     };
 
-    // Agent verifies payment before trading
+    // Try to use after expiration
     ts::next_tx(&mut scenario, AGENT);
     {
-        let mut trading_service = ts::take_shared<TradingService>(&scenario);
-        let apex_service = ts::take_shared<ServiceProvider>(&scenario);
+        let service = ts::take_shared<ServiceProvider>(&scenario);
         let mut capability = ts::take_from_sender<AccessCapability>(&scenario);
         let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
-        clock::set_for_testing(&mut clock, 2000);
+        clock::set_for_testing(&mut clock, 1000 + 3600_001); // Just past expiry
 
-        let verified = apex_trading::verify_trade_payment(
-            &mut trading_service,
-            &apex_service,
+        // This should fail - capability expired
+        apex_payments::use_access(
             &mut capability,
-            1, // consume 1 unit
+            &service,
+            1,
             &clock,
             ts::ctx(&mut scenario)
         );
 
-        assert!(verified, 0);
-        assert!(apex_payments::capability_remaining(&capability) == 99, 1);
-        assert!(apex_trading::trading_service_total_trades(&trading_service) == 1, 2);
-
         clock::destroy_for_testing(clock);
         ts::return_to_sender(&scenario, capability);
-        ts::return_shared(apex_service);
-        ts::return_shared(trading_service);
+        ts::return_shared(service);
     };
 
     ts::end(scenario);
 }
 
-// ==================== Atomic PTB Pattern Tests ====================
-// These tests demonstrate multi-operation sequences that mirror PTB behavior
-
 #[test]
-fun test_atomic_purchase_and_use() {
+fun test_extend_access_before_expiry() {
     let mut scenario = ts::begin(ADMIN);
-    setup_protocol(&mut scenario);
-
-    // Register service
-    ts::next_tx(&mut scenario, PROVIDER);
-    {
-        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
-        apex_payments::register_service(
-            &mut config,
-            b"API",
-            b"Test",
-            10_000_000,
-            mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
-            ts::ctx(&mut scenario)
-        );
-        ts::return_shared(config);
-    };
+    setup_grace_capability(&mut scenario, 0);
 
-    // Simulate PTB: purchase access + use access in same transaction
+    // One minute before expiry, extend by another hour
     ts::next_tx(&mut scenario, AGENT);
     {
         let mut config = ts::take_shared<ProtocolConfig>(&scenario);
         let mut service = ts::take_shared<ServiceProvider>(&scenario);
+        let mut capability = ts::take_from_sender<AccessCapability>(&scenario);
         let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
-        clock::set_for_testing(&mut clock, 1000);
+        clock::set_for_testing(&mut clock, 1000 + 3600_000 - 60_000);
 
-        // Command 1: Purchase access
-        let mut capability = apex_payments::purchase_access(
+        let revenue_before = apex_payments::service_revenue(&service);
+        let treasury_before = apex_payments::treasury_balance(&config);
+        apex_payments::extend_access(
             &mut config,
             &mut service,
+            &mut capability,
             mint_sui(1 * MIST_PER_SUI, ts::ctx(&mut scenario)),
-            100,
             3600_000,
-            0,
-            &clock,
-            ts::ctx(&mut scenario)
-        );
-
-        // Command 2: Use access (in same tx, capability passed between commands)
-        let success = apex_payments::use_access(
-            &mut capability,
-            &service,
-            5,
             &clock,
             ts::ctx(&mut scenario)
         );
+        assert!(apex_payments::capability_expires_at(&capability) == 1000 + 2 * 3600_000, 0);
 
-        assert!(success, 0);
-        assert!(apex_payments::capability_remaining(&capability) == 95, 1);
-
-        // Command 3: Transfer to recipient
-        transfer::public_transfer(capability, AGENT);
+        // Renewal costs one unit, split between provider and treasury
+        let revenue_gain = apex_payments::service_revenue(&service) - revenue_before;
+        let fee_gain = apex_payments::treasury_balance(&config) - treasury_before;
+        assert!(revenue_gain + fee_gain == 10_000_000, 1);
 
+        ts::return_to_sender(&scenario, capability);
         clock::destroy_for_testing(clock);
         ts::return_shared(service);
         ts::return_shared(config);
     };
 
-    ts::end(scenario);
-}
-
-#[test]
-fun test_protocol_pause() {
-    let mut scenario = ts::begin(ADMIN);
-    setup_protocol(&mut scenario);
-
-    // Admin pauses protocol
-    ts::next_tx(&mut scenario, ADMIN);
+    // Usable past the original deadline
+    ts::next_tx(&mut scenario, AGENT);
     {
-        let admin_cap = ts::take_from_sender<AdminCap>(&scenario);
-        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        let service = ts::take_shared<ServiceProvider>(&scenario);
+        let mut capability = ts::take_from_sender<AccessCapability>(&scenario);
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 1000 + 3600_000 + 30 * 60_000);
 
-        apex_payments::set_protocol_paused(&admin_cap, &mut config, true);
-        assert!(apex_payments::protocol_is_paused(&config), 0);
+        apex_payments::use_access(&mut capability, &service, 1, &clock, ts::ctx(&mut scenario));
+        assert!(apex_payments::capability_remaining(&capability) == 99, 2);
 
-        ts::return_to_sender(&scenario, admin_cap);
-        ts::return_shared(config);
+        ts::return_to_sender(&scenario, capability);
+        clock::destroy_for_testing(clock);
+        ts::return_shared(service);
     };
 
     ts::end(scenario);
 }
 
 #[test]
-#[expected_failure(abort_code = apex_payments::EProtocolPaused)]
-fun test_register_while_paused() {
+#[expected_failure(abort_code = apex_payments::EExpired)]
+fun test_extend_lapsed_access() {
     let mut scenario = ts::begin(ADMIN);
-    setup_protocol(&mut scenario);
+    setup_grace_capability(&mut scenario, 0);
 
-    // Admin pauses protocol
-    ts::next_tx(&mut scenario, ADMIN);
+    ts::next_tx(&mut scenario, AGENT);
     {
-        let admin_cap = ts::take_from_sender<AdminCap>(&scenario);
         let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        let mut service = ts::take_shared<ServiceProvider>(&scenario);
+        let mut capability = ts::take_from_sender<AccessCapability>(&scenario);
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 1000 + 3600_001);
 
-        apex_payments::set_protocol_paused(&admin_cap, &mut config, true);
+        apex_payments::extend_access(
+            &mut config,
+            &mut service,
+            &mut capability,
+            mint_sui(1 * MIST_PER_SUI, ts::ctx(&mut scenario)),
+            3600_000,
+            &clock,
+            ts::ctx(&mut scenario)
+        );
 
-        ts::return_to_sender(&scenario, admin_cap);
+        ts::return_to_sender(&scenario, capability);
+        clock::destroy_for_testing(clock);
+        ts::return_shared(service);
         ts::return_shared(config);
     };
 
-    // Try to register while paused
+    ts::end(scenario);
+}
+
+#[test]
+#[expected_failure(abort_code = apex_payments::EStaleService)]
+fun test_purchase_with_stale_service_version() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
     ts::next_tx(&mut scenario, PROVIDER);
     {
         let mut config = ts::take_shared<ProtocolConfig>(&scenario);
-
         apex_payments::register_service(
             &mut config,
-            b"Test",
+            b"API",
             b"Test",
             10_000_000,
             mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
             ts::ctx(&mut scenario)
         );
-
         ts::return_shared(config);
     };
 
-    ts::end(scenario);
-}
-
-// ==================== Agent Authorization Tests ====================
+    // Agent reads the service version it is quoting against
+    ts::next_tx(&mut scenario, AGENT);
+    let quoted_version = {
+        let service = ts::take_shared<ServiceProvider>(&scenario);
+        let version = apex_payments::service_version(&service);
+        ts::return_shared(service);
+        version
+    };
 
-#[test]
-fun test_create_authorization() {
-    let mut scenario = ts::begin(OWNER);
+    // Provider raises the price in a separate transaction
+    ts::next_tx(&mut scenario, PROVIDER);
+    {
+        let mut service = ts::take_shared<ServiceProvider>(&scenario);
+        apex_payments::update_service_price(&mut service, 20_000_000, ts::ctx(&mut scenario));
+        assert!(apex_payments::service_version(&service) == quoted_version + 1, 0);
+        ts::return_shared(service);
+    };
 
-    // Owner creates authorization for agent
-    ts::next_tx(&mut scenario, OWNER);
+    // Purchase built from the stale quote must abort
+    ts::next_tx(&mut scenario, AGENT);
     {
-        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
-        clock::set_for_testing(&mut clock, 1000);
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        let mut service = ts::take_shared<ServiceProvider>(&scenario);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
 
-        let auth = apex_payments::create_authorization(
-            AGENT, // authorized agent
-            vector::empty(), // all services allowed
-            100_000_000, // 0.1 SUI spend limit per tx
-            1_000_000_000, // 1 SUI daily limit
-            86400_000, // 24 hour duration
+        let capability = apex_payments::purchase_access_at_version(
+            &mut config,
+            &mut service,
+            quoted_version,
+            mint_sui(1 * MIST_PER_SUI, ts::ctx(&mut scenario)),
+            10,
+            0,
+            0,
             &clock,
             ts::ctx(&mut scenario)
         );
 
-        // Verify authorization
-        assert!(apex_payments::authorization_owner(&auth) == OWNER, 0);
-        assert!(apex_payments::authorization_agent(&auth) == AGENT, 1);
-        assert!(!apex_payments::authorization_is_paused(&auth), 2);
-
-        transfer::public_transfer(auth, OWNER);
+        transfer::public_transfer(capability, AGENT);
         clock::destroy_for_testing(clock);
+        ts::return_shared(service);
+        ts::return_shared(config);
     };
 
     ts::end(scenario);
 }
 
 #[test]
-fun test_authorized_purchase() {
+fun test_purchase_after_refreshing_service_version() {
     let mut scenario = ts::begin(ADMIN);
     setup_protocol(&mut scenario);
 
-    // Register service
     ts::next_tx(&mut scenario, PROVIDER);
     {
         let mut config = ts::take_shared<ProtocolConfig>(&scenario);
         apex_payments::register_service(
             &mut config,
-            b"API Service",
-            b"Test service",
-            10_000_000, // 0.01 SUI per unit
+            b"API",
+            b"Test",
+            10_000_000,
             mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
             ts::ctx(&mut scenario)
         );
         ts::return_shared(config);
     };
 
-    // Owner creates authorization for agent
-    ts::next_tx(&mut scenario, OWNER);
-    {
-        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
-        clock::set_for_testing(&mut clock, 1000);
-
-        let auth = apex_payments::create_authorization(
-            AGENT,
-            vector::empty(), // all services allowed
-            500_000_000, // 0.5 SUI spend limit per tx
-            1_000_000_000, // 1 SUI daily limit
-            86400_000, // 24 hours
-            &clock,
-            ts::ctx(&mut scenario)
-        );
+    // Agent reads the service version it is quoting against
+    ts::next_tx(&mut scenario, AGENT);
+    let quoted_version = {
+        let service = ts::take_shared<ServiceProvider>(&scenario);
+        let version = apex_payments::service_version(&service);
+        ts::return_shared(service);
+        version
+    };
 
-        transfer::public_transfer(auth, AGENT);
-        clock::destroy_for_testing(clock);
+    // Provider raises the price in a separate transaction
+    ts::next_tx(&mut scenario, PROVIDER);
+    {
+        let mut service = ts::take_shared<ServiceProvider>(&scenario);
+        apex_payments::update_service_price(&mut service, 20_000_000, ts::ctx(&mut scenario));
+        assert!(apex_payments::service_version(&service) == quoted_version + 1, 0);
+        ts::return_shared(service);
     };
 
-    // Agent uses authorization to purchase access
+    // After refreshing, the purchase succeeds at the new price
     ts::next_tx(&mut scenario, AGENT);
     {
         let mut config = ts::take_shared<ProtocolConfig>(&scenario);
         let mut service = ts::take_shared<ServiceProvider>(&scenario);
-        let mut auth = ts::take_from_sender<AgentAuthorization>(&scenario);
-        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
-        clock::set_for_testing(&mut clock, 2000);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        let current_version = apex_payments::service_version(&service);
 
-        let payment = mint_sui(100_000_000, ts::ctx(&mut scenario)); // 0.1 SUI for 10 units
-
-        let capability = apex_payments::authorized_purchase(
-            &mut auth,
+        let capability = apex_payments::purchase_access_at_version(
             &mut config,
             &mut service,
-            payment,
-            10, // 10 units
-            3600_000, // 1 hour
-            0, // no rate limit
+            current_version,
+            mint_sui(10 * 20_000_000, ts::ctx(&mut scenario)),
+            10,
+            0,
+            0,
             &clock,
             ts::ctx(&mut scenario)
         );
-
-        assert!(apex_payments::capability_remaining(&capability) == 10, 0);
+        assert!(apex_payments::capability_remaining(&capability) == 10, 1);
 
         transfer::public_transfer(capability, AGENT);
         clock::destroy_for_testing(clock);
-        ts::return_to_sender(&scenario, auth);
         ts::return_shared(service);
         ts::return_shared(config);
     };
@@ -1224,74 +1333,77 @@ fun test_authorized_purchase() {
 }
 
 #[test]
-#[expected_failure(abort_code = apex_payments::EExceededLimit)]
-fun test_authorized_purchase_exceeds_limit() {
+fun test_bundle_shared_unit_pool() {
     let mut scenario = ts::begin(ADMIN);
     setup_protocol(&mut scenario);
 
-    // Register service
-    ts::next_tx(&mut scenario, PROVIDER);
-    {
+    // Provider registers three services at different prices
+    let mut service_ids = vector[];
+    let prices = vector[10_000_000, 20_000_000, 30_000_000];
+    let mut i = 0;
+    while (i < 3) {
+        ts::next_tx(&mut scenario, PROVIDER);
         let mut config = ts::take_shared<ProtocolConfig>(&scenario);
         apex_payments::register_service(
             &mut config,
             b"API",
             b"Test",
-            10_000_000,
+            prices[i],
             mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
             ts::ctx(&mut scenario)
         );
         ts::return_shared(config);
+        ts::next_tx(&mut scenario, PROVIDER);
+        service_ids.push_back(ts::most_recent_id_shared<ServiceProvider>().destroy_some());
+        i = i + 1;
     };
 
-    // Owner creates authorization with low spend limit
-    ts::next_tx(&mut scenario, OWNER);
+    // Agent buys a bundle covering only the first two services
+    ts::next_tx(&mut scenario, AGENT);
     {
-        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
-        clock::set_for_testing(&mut clock, 1000);
+        let config = ts::take_shared<ProtocolConfig>(&scenario);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
 
-        let auth = apex_payments::create_authorization(
-            AGENT,
-            vector::empty(),
-            50_000_000, // Only 0.05 SUI spend limit per tx
-            1_000_000_000,
-            86400_000,
+        let bundle = apex_payments::purchase_bundle(
+            &config,
+            vector[service_ids[0], service_ids[1]],
+            mint_sui(1 * MIST_PER_SUI, ts::ctx(&mut scenario)),
+            100,
+            0,
             &clock,
             ts::ctx(&mut scenario)
         );
+        assert!(apex_payments::bundle_remaining(&bundle) == 100, 0);
+        transfer::public_transfer(bundle, AGENT);
 
-        transfer::public_transfer(auth, AGENT);
         clock::destroy_for_testing(clock);
+        ts::return_shared(config);
     };
 
-    // Agent tries to purchase more than limit
+    // Use units against each included service from the same pool
     ts::next_tx(&mut scenario, AGENT);
     {
         let mut config = ts::take_shared<ProtocolConfig>(&scenario);
-        let mut service = ts::take_shared<ServiceProvider>(&scenario);
-        let mut auth = ts::take_from_sender<AgentAuthorization>(&scenario);
-        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
-        clock::set_for_testing(&mut clock, 2000);
+        let mut service_a = ts::take_shared_by_id<ServiceProvider>(&scenario, service_ids[0]);
+        let mut service_b = ts::take_shared_by_id<ServiceProvider>(&scenario, service_ids[1]);
+        let mut bundle = ts::take_from_sender<BundleCapability>(&scenario);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
 
-        // Try to pay 0.1 SUI (exceeds 0.05 limit)
-        let payment = mint_sui(100_000_000, ts::ctx(&mut scenario));
+        apex_payments::use_bundle(&mut bundle, &mut config, &mut service_a, 3, &clock);
+        assert!(apex_payments::bundle_remaining(&bundle) == 97, 1);
 
-        let capability = apex_payments::authorized_purchase(
-            &mut auth,
-            &mut config,
-            &mut service,
-            payment,
-            10, // 10 units = 0.1 SUI
-            3600_000,
-            0,
-            &clock,
-            ts::ctx(&mut scenario)
-        );
+        apex_payments::use_bundle(&mut bundle, &mut config, &mut service_b, 4, &clock);
+        assert!(apex_payments::bundle_remaining(&bundle) == 93, 2);
+
+        // Escrow drawn at each service's own price: 3 * 0.01 + 4 * 0.02 SUI
+        assert!(apex_payments::bundle_escrow(&bundle) == 1 * MIST_PER_SUI - 110_000_000, 3);
+        assert!(apex_payments::service_total_served(&service_a) == 3, 4);
+        assert!(apex_payments::service_total_served(&service_b) == 4, 5);
 
-        transfer::public_transfer(capability, AGENT);
         clock::destroy_for_testing(clock);
-        ts::return_to_sender(&scenario, auth);
-        ts::return_shared(service);
+        ts::return_to_sender(&scenario, bundle);
+        ts::return_shared(service_b);
+        ts::return_shared(service_a);
         ts::return_shared(config);
     };
 
@@ -1299,300 +1411,3677 @@ fun test_authorized_purchase_exceeds_limit() {
 }
 
 #[test]
-fun test_pause_and_revoke_authorization() {
-    let mut scenario = ts::begin(OWNER);
+#[expected_failure(abort_code = apex_payments::EInvalidCapability)]
+fun test_bundle_rejects_unlisted_service() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
 
-    // Owner creates authorization
-    ts::next_tx(&mut scenario, OWNER);
+    // Provider registers three services at different prices
+    let mut service_ids = vector[];
+    let prices = vector[10_000_000, 20_000_000, 30_000_000];
+    let mut i = 0;
+    while (i < 3) {
+        ts::next_tx(&mut scenario, PROVIDER);
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        apex_payments::register_service(
+            &mut config,
+            b"API",
+            b"Test",
+            prices[i],
+            mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
+            ts::ctx(&mut scenario)
+        );
+        ts::return_shared(config);
+        ts::next_tx(&mut scenario, PROVIDER);
+        service_ids.push_back(ts::most_recent_id_shared<ServiceProvider>().destroy_some());
+        i = i + 1;
+    };
+
+    // Agent buys a bundle covering only the first two services
+    ts::next_tx(&mut scenario, AGENT);
     {
-        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
-        clock::set_for_testing(&mut clock, 1000);
+        let config = ts::take_shared<ProtocolConfig>(&scenario);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
 
-        let auth = apex_payments::create_authorization(
-            AGENT,
-            vector::empty(),
-            100_000_000,
-            1_000_000_000,
-            86400_000,
+        let bundle = apex_payments::purchase_bundle(
+            &config,
+            vector[service_ids[0], service_ids[1]],
+            mint_sui(1 * MIST_PER_SUI, ts::ctx(&mut scenario)),
+            100,
+            0,
             &clock,
             ts::ctx(&mut scenario)
         );
+        assert!(apex_payments::bundle_remaining(&bundle) == 100, 0);
+        transfer::public_transfer(bundle, AGENT);
 
-        transfer::public_transfer(auth, OWNER);
         clock::destroy_for_testing(clock);
+        ts::return_shared(config);
     };
 
-    // Owner pauses authorization
-    ts::next_tx(&mut scenario, OWNER);
+    ts::next_tx(&mut scenario, AGENT);
     {
-        let mut auth = ts::take_from_sender<AgentAuthorization>(&scenario);
-
-        apex_payments::pause_authorization(&mut auth, ts::ctx(&mut scenario));
-        assert!(apex_payments::authorization_is_paused(&auth), 0);
-
-        // Owner unpauses
-        apex_payments::unpause_authorization(&mut auth, ts::ctx(&mut scenario));
-        assert!(!apex_payments::authorization_is_paused(&auth), 1);
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        let mut service_c = ts::take_shared_by_id<ServiceProvider>(&scenario, service_ids[2]);
+        let mut bundle = ts::take_from_sender<BundleCapability>(&scenario);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
 
-        ts::return_to_sender(&scenario, auth);
-    };
+        // Third service is not part of the bundle
+        apex_payments::use_bundle(&mut bundle, &mut config, &mut service_c, 1, &clock);
 
-    // Owner revokes authorization
-    ts::next_tx(&mut scenario, OWNER);
-    {
-        let auth = ts::take_from_sender<AgentAuthorization>(&scenario);
-        apex_payments::revoke_authorization(auth, ts::ctx(&mut scenario));
+        clock::destroy_for_testing(clock);
+        ts::return_to_sender(&scenario, bundle);
+        ts::return_shared(service_c);
+        ts::return_shared(config);
     };
 
     ts::end(scenario);
 }
 
-// ==================== Trusted Meter Tests ====================
+/// Register a service available from 1_000ms to 5_000ms and have AGENT buy
+/// a bundle covering it; returns the service ID
+fun setup_windowed_bundle(scenario: &mut Scenario): ID {
+    ts::next_tx(scenario, PROVIDER);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(scenario);
+        apex_payments::register_service_with_availability(
+            &mut config,
+            b"Market Hours API",
+            b"Only sold during the window",
+            10_000_000,
+            1_000,
+            5_000,
+            mint_sui(REGISTRATION_FEE, ts::ctx(scenario)),
+            ts::ctx(scenario)
+        );
+        ts::return_shared(config);
+    };
+    ts::next_tx(scenario, AGENT);
+    let service_id = ts::most_recent_id_shared<ServiceProvider>().destroy_some();
 
-#[test]
-fun test_register_meter() {
-    let mut scenario = ts::begin(ADMIN);
-    setup_protocol(&mut scenario);
+    {
+        let config = ts::take_shared<ProtocolConfig>(scenario);
+        let clock = clock::create_for_testing(ts::ctx(scenario));
 
-    // Admin registers a trusted meter
-    ts::next_tx(&mut scenario, ADMIN);
+        let bundle = apex_payments::purchase_bundle(
+            &config,
+            vector[service_id],
+            mint_sui(1 * MIST_PER_SUI, ts::ctx(scenario)),
+            10,
+            0,
+            &clock,
+            ts::ctx(scenario)
+        );
+        transfer::public_transfer(bundle, AGENT);
+
+        clock::destroy_for_testing(clock);
+        ts::return_shared(config);
+    };
+
+    service_id
+}
+
+/// Have AGENT draw one unit from its bundle at `now_ms`
+fun use_windowed_bundle_at(scenario: &mut Scenario, service_id: ID, now_ms: u64) {
+    ts::next_tx(scenario, AGENT);
     {
-        let admin_cap = ts::take_from_sender<AdminCap>(&scenario);
+        let mut config = ts::take_shared<ProtocolConfig>(scenario);
+        let mut service = ts::take_shared_by_id<ServiceProvider>(scenario, service_id);
+        let mut bundle = ts::take_from_sender<BundleCapability>(scenario);
+        let mut clock = clock::create_for_testing(ts::ctx(scenario));
+        clock::set_for_testing(&mut clock, now_ms);
 
-        // 32-byte Ed25519 pubkey (simulated)
-        let enclave_pubkey = x"0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
-        let pcr_values = b"pcr0:abc123";
+        apex_payments::use_bundle(&mut bundle, &mut config, &mut service, 1, &clock);
 
-        let meter = apex_payments::register_meter(
-            &admin_cap,
-            enclave_pubkey,
-            pcr_values,
-            b"Test Nautilus Meter",
-            ts::ctx(&mut scenario)
-        );
+        clock::destroy_for_testing(clock);
+        ts::return_to_sender(scenario, bundle);
+        ts::return_shared(service);
+        ts::return_shared(config);
+    };
+}
 
-        assert!(apex_payments::meter_is_active(&meter), 0);
-        assert!(apex_payments::meter_pubkey(&meter) == enclave_pubkey, 1);
+#[test]
+fun test_use_bundle_within_availability_window() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+    let service_id = setup_windowed_bundle(&mut scenario);
+    use_windowed_bundle_at(&mut scenario, service_id, 2_000);
 
-        transfer::public_transfer(meter, ADMIN);
-        ts::return_to_sender(&scenario, admin_cap);
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let bundle = ts::take_from_sender<BundleCapability>(&scenario);
+        assert!(apex_payments::bundle_remaining(&bundle) == 9, 0);
+        ts::return_to_sender(&scenario, bundle);
     };
 
     ts::end(scenario);
 }
 
 #[test]
-fun test_deactivate_meter() {
+#[expected_failure(abort_code = apex_payments::EServiceUnavailable)]
+fun test_use_bundle_outside_availability_window() {
     let mut scenario = ts::begin(ADMIN);
     setup_protocol(&mut scenario);
+    let service_id = setup_windowed_bundle(&mut scenario);
+    use_windowed_bundle_at(&mut scenario, service_id, 6_000);
+    ts::end(scenario);
+}
+
+#[test]
+#[expected_failure(abort_code = apex_payments::EProtocolPaused)]
+fun test_use_bundle_while_paused() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+    let service_id = setup_windowed_bundle(&mut scenario);
 
-    // Register meter
     ts::next_tx(&mut scenario, ADMIN);
     {
         let admin_cap = ts::take_from_sender<AdminCap>(&scenario);
-        let enclave_pubkey = x"0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
-
-        let meter = apex_payments::register_meter(
-            &admin_cap,
-            enclave_pubkey,
-            b"pcr0:abc",
-            b"Test Meter",
-            ts::ctx(&mut scenario)
-        );
-
-        transfer::public_transfer(meter, ADMIN);
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        apex_payments::set_protocol_paused(&admin_cap, &mut config, true);
         ts::return_to_sender(&scenario, admin_cap);
+        ts::return_shared(config);
     };
 
-    // Deactivate meter
-    ts::next_tx(&mut scenario, ADMIN);
+    use_windowed_bundle_at(&mut scenario, service_id, 2_000);
+    ts::end(scenario);
+}
+
+#[test]
+#[expected_failure(abort_code = apex_payments::EInvalidInput)]
+fun test_bundle_rejects_unregistered_service_id() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    ts::next_tx(&mut scenario, AGENT);
     {
-        let admin_cap = ts::take_from_sender<AdminCap>(&scenario);
-        let mut meter = ts::take_from_sender<TrustedMeter>(&scenario);
+        let config = ts::take_shared<ProtocolConfig>(&scenario);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
 
-        apex_payments::deactivate_meter(&admin_cap, &mut meter);
-        assert!(!apex_payments::meter_is_active(&meter), 0);
+        // Not the ID of any service registered through this config
+        let bundle = apex_payments::purchase_bundle(
+            &config,
+            vector[object::id_from_address(@0xBAD)],
+            mint_sui(1 * MIST_PER_SUI, ts::ctx(&mut scenario)),
+            10,
+            0,
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+        transfer::public_transfer(bundle, AGENT);
 
-        ts::return_to_sender(&scenario, meter);
-        ts::return_to_sender(&scenario, admin_cap);
+        clock::destroy_for_testing(clock);
+        ts::return_shared(config);
     };
 
     ts::end(scenario);
 }
 
-// ==================== Service Registry Tests ====================
-
 #[test]
-fun test_create_registry() {
+#[expected_failure(abort_code = apex_payments::EInvalidInput)]
+fun test_bundle_rejects_duplicate_service_id() {
     let mut scenario = ts::begin(ADMIN);
     setup_protocol(&mut scenario);
 
-    // Admin creates registry
-    ts::next_tx(&mut scenario, ADMIN);
+    ts::next_tx(&mut scenario, PROVIDER);
     {
-        let admin_cap = ts::take_from_sender<AdminCap>(&scenario);
-
-        apex_payments::create_registry(&admin_cap, ts::ctx(&mut scenario));
-
-        ts::return_to_sender(&scenario, admin_cap);
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        apex_payments::register_service(
+            &mut config,
+            b"API",
+            b"Test",
+            10_000_000,
+            mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
+            ts::ctx(&mut scenario)
+        );
+        ts::return_shared(config);
     };
 
-    // Verify registry was created
-    ts::next_tx(&mut scenario, ADMIN);
+    ts::next_tx(&mut scenario, AGENT);
     {
-        let registry = ts::take_shared<ServiceRegistry>(&scenario);
-        assert!(apex_payments::registry_count(&registry) == 0, 0);
-        ts::return_shared(registry);
+        let config = ts::take_shared<ProtocolConfig>(&scenario);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        let service_id = ts::most_recent_id_shared<ServiceProvider>().destroy_some();
+
+        let bundle = apex_payments::purchase_bundle(
+            &config,
+            vector[service_id, service_id],
+            mint_sui(1 * MIST_PER_SUI, ts::ctx(&mut scenario)),
+            10,
+            0,
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+        transfer::public_transfer(bundle, AGENT);
+
+        clock::destroy_for_testing(clock);
+        ts::return_shared(config);
     };
 
     ts::end(scenario);
 }
 
+// ==================== Streaming Payment Tests ====================
+
 #[test]
-fun test_list_and_delist_service() {
+fun test_open_and_consume_stream() {
     let mut scenario = ts::begin(ADMIN);
     setup_protocol(&mut scenario);
 
-    // Register a service
+    // Register service
     ts::next_tx(&mut scenario, PROVIDER);
     {
         let mut config = ts::take_shared<ProtocolConfig>(&scenario);
         apex_payments::register_service(
             &mut config,
-            b"Oracle Service",
-            b"Price feed oracle",
-            5_000_000,
+            b"Streaming API",
+            b"Pay per second service",
+            1_000_000, // 0.001 SUI per unit
             mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
             ts::ctx(&mut scenario)
         );
         ts::return_shared(config);
     };
 
-    // Admin creates registry
-    ts::next_tx(&mut scenario, ADMIN);
-    {
-        let admin_cap = ts::take_from_sender<AdminCap>(&scenario);
-        apex_payments::create_registry(&admin_cap, ts::ctx(&mut scenario));
-        ts::return_to_sender(&scenario, admin_cap);
-    };
-
-    // Provider lists service in registry
-    ts::next_tx(&mut scenario, PROVIDER);
+    // Open stream
+    ts::next_tx(&mut scenario, AGENT);
     {
+        let config = ts::take_shared<ProtocolConfig>(&scenario);
         let service = ts::take_shared<ServiceProvider>(&scenario);
-        let mut registry = ts::take_shared<ServiceRegistry>(&scenario);
         let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
         clock::set_for_testing(&mut clock, 1000);
 
-        apex_payments::list_service(
-            &mut registry,
+        let escrow = mint_sui(10 * MIST_PER_SUI, ts::ctx(&mut scenario)); // 10 SUI
+
+        apex_payments::open_stream(
+            &config,
             &service,
-            b"oracle",
-            b"walrus_blob_id_123",
+            escrow,
+            10000, // max 10000 units
             &clock,
             ts::ctx(&mut scenario)
         );
 
-        assert!(apex_payments::registry_count(&registry) == 1, 0);
-
         clock::destroy_for_testing(clock);
-        ts::return_shared(registry);
         ts::return_shared(service);
+        ts::return_shared(config);
     };
 
-    // Provider delists service
+    // Provider records consumption
     ts::next_tx(&mut scenario, PROVIDER);
     {
-        let service = ts::take_shared<ServiceProvider>(&scenario);
-        let mut registry = ts::take_shared<ServiceRegistry>(&scenario);
+        let mut stream = ts::take_shared<PaymentStream>(&scenario);
+        let mut service = ts::take_shared<ServiceProvider>(&scenario);
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 5000);
 
-        apex_payments::delist_service(
-            &mut registry,
-            &service,
+        apex_payments::record_stream_consumption(
+            &mut stream,
+            &mut service,
+            100, // 100 units consumed
+            &clock,
             ts::ctx(&mut scenario)
         );
 
-        assert!(apex_payments::registry_count(&registry) == 0, 0);
+        assert!(apex_payments::stream_consumed(&stream) == 100, 0);
 
-        ts::return_shared(registry);
+        clock::destroy_for_testing(clock);
         ts::return_shared(service);
+        ts::return_shared(stream);
     };
 
     ts::end(scenario);
 }
 
-#[test]
-fun test_set_featured_service() {
-    let mut scenario = ts::begin(ADMIN);
-    setup_protocol(&mut scenario);
+/// Streaming service, a 10 SUI stream opened by AGENT and a TrustedMeter
+/// held by PROVIDER
+fun setup_verified_stream(scenario: &mut Scenario) {
+    setup_protocol(scenario);
 
-    // Register a service
-    ts::next_tx(&mut scenario, PROVIDER);
+    ts::next_tx(scenario, PROVIDER);
     {
-        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        let mut config = ts::take_shared<ProtocolConfig>(scenario);
         apex_payments::register_service(
             &mut config,
-            b"Featured Service",
-            b"A great service",
-            10_000_000,
-            mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
-            ts::ctx(&mut scenario)
+            b"Streaming API",
+            b"Pay per second service",
+            1_000_000,
+            mint_sui(REGISTRATION_FEE, ts::ctx(scenario)),
+            ts::ctx(scenario)
         );
         ts::return_shared(config);
     };
 
-    // Admin creates registry
-    ts::next_tx(&mut scenario, ADMIN);
+    ts::next_tx(scenario, AGENT);
     {
-        let admin_cap = ts::take_from_sender<AdminCap>(&scenario);
-        apex_payments::create_registry(&admin_cap, ts::ctx(&mut scenario));
-        ts::return_to_sender(&scenario, admin_cap);
+        let config = ts::take_shared<ProtocolConfig>(scenario);
+        let service = ts::take_shared<ServiceProvider>(scenario);
+        let clock = clock::create_for_testing(ts::ctx(scenario));
+        apex_payments::open_stream(
+            &config,
+            &service,
+            mint_sui(10 * MIST_PER_SUI, ts::ctx(scenario)),
+            10000,
+            &clock,
+            ts::ctx(scenario)
+        );
+        clock::destroy_for_testing(clock);
+        ts::return_shared(service);
+        ts::return_shared(config);
     };
 
-    // Provider lists service
+    ts::next_tx(scenario, ADMIN);
+    {
+        let admin_cap = ts::take_from_sender<AdminCap>(scenario);
+        let meter = apex_payments::register_meter(
+            &admin_cap,
+            x"0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef",
+            b"pcr0:abc123",
+            b"Stream Meter",
+            ts::ctx(scenario)
+        );
+        transfer::public_transfer(meter, PROVIDER);
+        ts::return_to_sender(scenario, admin_cap);
+    };
+}
+
+#[test]
+#[expected_failure(abort_code = apex_payments::EUnauthorized)]
+fun test_verified_batch_with_tampered_report_reverts() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_verified_stream(&mut scenario);
+
+    // The third signature was not produced by the meter's key, so the
+    // whole batch aborts and none of the reports are consumed
     ts::next_tx(&mut scenario, PROVIDER);
     {
-        let service = ts::take_shared<ServiceProvider>(&scenario);
-        let mut registry = ts::take_shared<ServiceRegistry>(&scenario);
+        let mut stream = ts::take_shared<PaymentStream>(&scenario);
+        let mut service = ts::take_shared<ServiceProvider>(&scenario);
+        let meter = ts::take_from_sender<TrustedMeter>(&scenario);
         let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
-        clock::set_for_testing(&mut clock, 1000);
+        clock::set_for_testing(&mut clock, 10_000);
 
-        apex_payments::list_service(
-            &mut registry,
-            &service,
-            b"defi",
-            b"blob_id",
+        let mut tampered = vector::empty<u8>();
+        let mut i = 0;
+        while (i < 64) {
+            vector::push_back(&mut tampered, 0xAB);
+            i = i + 1;
+        };
+
+        apex_payments::record_verified_consumption_batch(
+            &mut stream,
+            &mut service,
+            &meter,
+            vector[10, 20, 30],
+            vector[9_000, 9_500, 10_000],
+            vector[tampered, tampered, tampered],
             &clock,
             ts::ctx(&mut scenario)
         );
 
         clock::destroy_for_testing(clock);
-        ts::return_shared(registry);
+        ts::return_to_sender(&scenario, meter);
         ts::return_shared(service);
+        ts::return_shared(stream);
     };
 
-    // Admin sets featured status
-    ts::next_tx(&mut scenario, ADMIN);
-    {
-        let mut registry = ts::take_shared<ServiceRegistry>(&scenario);
-        let service = ts::take_shared<ServiceProvider>(&scenario);
-        let service_id = object::id(&service);
+    ts::end(scenario);
+}
 
-        apex_payments::set_featured(
-            &mut registry,
-            service_id,
-            true,
+#[test]
+#[expected_failure(abort_code = apex_payments::EInvalidInput)]
+fun test_verified_batch_rejects_mismatched_lengths() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_verified_stream(&mut scenario);
+
+    ts::next_tx(&mut scenario, PROVIDER);
+    {
+        let mut stream = ts::take_shared<PaymentStream>(&scenario);
+        let mut service = ts::take_shared<ServiceProvider>(&scenario);
+        let meter = ts::take_from_sender<TrustedMeter>(&scenario);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+
+        // Three reports but only two signatures
+        apex_payments::record_verified_consumption_batch(
+            &mut stream,
+            &mut service,
+            &meter,
+            vector[10, 20, 30],
+            vector[0, 0, 0],
+            vector[vector[], vector[]],
+            &clock,
             ts::ctx(&mut scenario)
         );
 
-        // Verify featured status via registry_get
-        let (id, _name, _category, _price, featured) = apex_payments::registry_get(&registry, 0);
-        assert!(id == service_id, 0);
-        assert!(featured == true, 1);
+        clock::destroy_for_testing(clock);
+        ts::return_to_sender(&scenario, meter);
+        ts::return_shared(service);
+        ts::return_shared(stream);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+#[expected_failure(abort_code = apex_payments::EUnauthorized)]
+fun test_verified_sessions_batch_with_tampered_report_reverts() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_verified_stream(&mut scenario);
+
+    // Three sessions closed together: one bad signature aborts the call, so
+    // none of the capabilities are charged
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        let mut service = ts::take_shared<ServiceProvider>(&scenario);
+        let meter = ts::take_from_address<TrustedMeter>(&scenario, PROVIDER);
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 10_000);
+
+        let mut caps = vector::empty<AccessCapability>();
+        let mut i = 0;
+        while (i < 3) {
+            caps.push_back(apex_payments::purchase_access(
+                &mut config,
+                &mut service,
+                mint_sui(5_000_000, ts::ctx(&mut scenario)),
+                5,
+                0,
+                0,
+                &clock,
+                ts::ctx(&mut scenario)
+            ));
+            i = i + 1;
+        };
+
+        let mut tampered = vector::empty<u8>();
+        i = 0;
+        while (i < 64) {
+            vector::push_back(&mut tampered, 0xAB);
+            i = i + 1;
+        };
+
+        apex_payments::close_verified_sessions_batch(
+            caps,
+            &service,
+            &meter,
+            vector[
+                apex_payments::new_session_report(1, 9_000),
+                apex_payments::new_session_report(2, 9_500),
+                apex_payments::new_session_report(3, 10_000),
+            ],
+            vector[tampered, tampered, tampered],
+            &clock,
+            ts::ctx(&mut scenario)
+        );
 
+        clock::destroy_for_testing(clock);
+        ts::return_to_address(PROVIDER, meter);
         ts::return_shared(service);
-        ts::return_shared(registry);
+        ts::return_shared(config);
+    };
+
+    ts::end(scenario);
+}
+
+// ==================== Meter-Required Service Tests ====================
+
+fun setup_metered_service(scenario: &mut Scenario) {
+    setup_protocol(scenario);
+
+    ts::next_tx(scenario, PROVIDER);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(scenario);
+        apex_payments::register_metered_service(
+            &mut config,
+            b"Sealed Dataset",
+            b"Decryption gated by a verified session",
+            1_000_000,
+            mint_sui(REGISTRATION_FEE, ts::ctx(scenario)),
+            ts::ctx(scenario)
+        );
+        ts::return_shared(config);
+    };
+
+    ts::next_tx(scenario, ADMIN);
+    {
+        let admin_cap = ts::take_from_sender<AdminCap>(scenario);
+        let meter = apex_payments::register_meter(
+            &admin_cap,
+            x"0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef",
+            b"pcr0:abc123",
+            b"Seal Meter",
+            ts::ctx(scenario)
+        );
+        transfer::public_transfer(meter, AGENT);
+        ts::return_to_sender(scenario, admin_cap);
+    };
+}
+
+#[test]
+#[expected_failure(abort_code = apex_payments::EMeterRequired)]
+fun test_meter_required_service_rejects_plain_purchase() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_metered_service(&mut scenario);
+
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        let mut service = ts::take_shared<ServiceProvider>(&scenario);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        assert!(apex_payments::service_requires_meter(&service), 0);
+
+        let cap = apex_payments::purchase_access(
+            &mut config,
+            &mut service,
+            mint_sui(10_000_000, ts::ctx(&mut scenario)),
+            10,
+            0,
+            0,
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        transfer::public_transfer(cap, AGENT);
+        clock::destroy_for_testing(clock);
+        ts::return_shared(service);
+        ts::return_shared(config);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+fun test_meter_required_service_sells_verified_session() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_metered_service(&mut scenario);
+
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        let mut service = ts::take_shared<ServiceProvider>(&scenario);
+        let meter = ts::take_from_sender<TrustedMeter>(&scenario);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+
+        let cap = apex_protocol::apex_workflows::open_verified_access_session(
+            &mut config,
+            &mut service,
+            &meter,
+            mint_sui(10_000_000, ts::ctx(&mut scenario)),
+            10,
+            0,
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+        assert!(apex_payments::capability_remaining(&cap) == 10, 0);
+        assert!(apex_payments::service_total_served(&service) == 10, 1);
+
+        transfer::public_transfer(cap, AGENT);
+        clock::destroy_for_testing(clock);
+        ts::return_to_sender(&scenario, meter);
+        ts::return_shared(service);
+        ts::return_shared(config);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+#[expected_failure(abort_code = apex_protocol::apex_workflows::EVerificationFailed)]
+fun test_verify_seal_access_rejects_bad_attestation() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_metered_service(&mut scenario);
+
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        let mut service = ts::take_shared<ServiceProvider>(&scenario);
+        let meter = ts::take_from_sender<TrustedMeter>(&scenario);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        let cap = apex_protocol::apex_workflows::open_verified_access_session(
+            &mut config,
+            &mut service,
+            &meter,
+            mint_sui(10_000_000, ts::ctx(&mut scenario)),
+            10,
+            0,
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+        transfer::public_transfer(cap, AGENT);
+        clock::destroy_for_testing(clock);
+        ts::return_to_sender(&scenario, meter);
+        ts::return_shared(service);
+        ts::return_shared(config);
+    };
+
+    // Every capability check passes; only the attestation is bad. The
+    // capability goes in by immutable reference, so the failed verify cannot
+    // have consumed any of its units
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let service = ts::take_shared<ServiceProvider>(&scenario);
+        let meter = ts::take_from_sender<TrustedMeter>(&scenario);
+        let cap = ts::take_from_sender<AccessCapability>(&scenario);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        assert!(apex_payments::capability_remaining(&cap) == 10, 0);
+
+        let mut content_id = object::id(&service).to_bytes();
+        content_id.append(b"doc-1");
+        let mut signature = vector::empty<u8>();
+        let mut i = 0;
+        while (i < 64) {
+            signature.push_back(0xAB);
+            i = i + 1;
+        };
+
+        apex_protocol::apex_workflows::verify_seal_access_atomic_for_testing(
+            &cap,
+            &service,
+            &meter,
+            content_id,
+            5,
+            signature,
+            0,
+            &clock
+        );
+
+        clock::destroy_for_testing(clock);
+        ts::return_to_sender(&scenario, cap);
+        ts::return_to_sender(&scenario, meter);
+        ts::return_shared(service);
+    };
+
+    ts::end(scenario);
+}
+
+// ==================== Agent Wallet Tests ====================
+
+#[test]
+fun test_create_agent_wallet() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    // Create agent wallet
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let config = ts::take_shared<ProtocolConfig>(&scenario);
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 1000);
+
+        let funding = mint_sui(5 * MIST_PER_SUI, ts::ctx(&mut scenario));
+
+        apex_payments::create_agent_wallet(
+            &config,
+            b"bot-001",
+            100_000_000, // 0.1 SUI spend limit
+            1_000_000_000, // 1 SUI daily limit
+            false, // restrict_funding
+            funding,
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        clock::destroy_for_testing(clock);
+        ts::return_shared(config);
+    };
+
+    // Verify wallet
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let wallet = ts::take_from_sender<AgentWallet>(&scenario);
+        assert!(apex_payments::agent_wallet_balance(&wallet) == 5 * MIST_PER_SUI, 0);
+        ts::return_to_sender(&scenario, wallet);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+fun test_agent_wallet_spending_limits() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    // Register service
+    ts::next_tx(&mut scenario, PROVIDER);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        apex_payments::register_service(
+            &mut config,
+            b"API",
+            b"Test",
+            10_000_000, // 0.01 SUI per unit
+            mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
+            ts::ctx(&mut scenario)
+        );
+        ts::return_shared(config);
+    };
+
+    // Create agent wallet
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let config = ts::take_shared<ProtocolConfig>(&scenario);
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 1000);
+
+        apex_payments::create_agent_wallet(
+            &config,
+            b"bot-001",
+            100_000_000, // 0.1 SUI spend limit per tx
+            1_000_000_000, // 1 SUI daily limit
+            false, // restrict_funding
+            mint_sui(5 * MIST_PER_SUI, ts::ctx(&mut scenario)),
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        clock::destroy_for_testing(clock);
+        ts::return_shared(config);
+    };
+
+    // Agent purchases access using wallet
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        let mut service = ts::take_shared<ServiceProvider>(&scenario);
+        let mut wallet = ts::take_from_sender<AgentWallet>(&scenario);
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 2000);
+
+        let capability = apex_payments::agent_purchase_access(
+            &mut wallet,
+            &mut config,
+            &mut service,
+            5, // 5 units = 0.05 SUI (under 0.1 limit)
+            3600_000,
+            0,
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        // Verify purchase worked
+        assert!(apex_payments::capability_remaining(&capability) == 5, 0);
+
+        // Check wallet balance decreased
+        assert!(apex_payments::agent_wallet_balance(&wallet) == 5 * MIST_PER_SUI - 50_000_000, 1);
+
+        transfer::public_transfer(capability, AGENT);
+        clock::destroy_for_testing(clock);
+        ts::return_to_sender(&scenario, wallet);
+        ts::return_shared(service);
+        ts::return_shared(config);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+#[expected_failure(abort_code = apex_payments::EExceededLimit)]
+fun test_agent_wallet_exceeds_spend_limit() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    // Register service
+    ts::next_tx(&mut scenario, PROVIDER);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        apex_payments::register_service(
+            &mut config,
+            b"API",
+            b"Test",
+            10_000_000,
+            mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
+            ts::ctx(&mut scenario)
+        );
+        ts::return_shared(config);
+    };
+
+    // Create agent wallet with low spend limit
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let config = ts::take_shared<ProtocolConfig>(&scenario);
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 1000);
+
+        apex_payments::create_agent_wallet(
+            &config,
+            b"bot-001",
+            50_000_000, // Only 0.05 SUI spend limit
+            1_000_000_000,
+            false, // restrict_funding
+            mint_sui(5 * MIST_PER_SUI, ts::ctx(&mut scenario)),
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        clock::destroy_for_testing(clock);
+        ts::return_shared(config);
+    };
+
+    // Try to purchase more than spend limit
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        let mut service = ts::take_shared<ServiceProvider>(&scenario);
+        let mut wallet = ts::take_from_sender<AgentWallet>(&scenario);
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 2000);
+
+        // Try to buy 10 units = 0.1 SUI (exceeds 0.05 limit)
+        let capability = apex_payments::agent_purchase_access(
+            &mut wallet,
+            &mut config,
+            &mut service,
+            10, // 0.1 SUI - exceeds limit!
+            3600_000,
+            0,
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        transfer::public_transfer(capability, AGENT);
+        clock::destroy_for_testing(clock);
+        ts::return_to_sender(&scenario, wallet);
+        ts::return_shared(service);
+        ts::return_shared(config);
+    };
+
+    ts::end(scenario);
+}
+
+// ==================== Shield Transfer Tests ====================
+
+#[test]
+fun test_shield_transfer_complete() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    // Sender initiates shield transfer
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let config = ts::take_shared<ProtocolConfig>(&scenario);
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 1000);
+
+        let payment = mint_sui(100 * MIST_PER_SUI, ts::ctx(&mut scenario));
+
+        // Hash of "secret-passphrase"
+        let secret_hash = sui::hash::keccak256(&b"secret-passphrase");
+
+        apex_payments::initiate_shield_transfer(
+            &config,
+            RECIPIENT,
+            payment,
+            86400_000, // 24 hours
+            secret_hash,
+            true, // require_secret
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        clock::destroy_for_testing(clock);
+        ts::return_shared(config);
+    };
+
+    // Recipient claims with correct secret
+    ts::next_tx(&mut scenario, RECIPIENT);
+    {
+        let session = ts::take_shared<ShieldSession>(&scenario);
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 5000);
+
+        apex_payments::complete_shield_transfer(
+            session,
+            b"secret-passphrase",
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        clock::destroy_for_testing(clock);
+    };
+
+    // Verify recipient received funds
+    ts::next_tx(&mut scenario, RECIPIENT);
+    {
+        let coin = ts::take_from_sender<Coin<SUI>>(&scenario);
+        assert!(coin::value(&coin) == 100 * MIST_PER_SUI, 0);
+        ts::return_to_sender(&scenario, coin);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+#[expected_failure(abort_code = apex_payments::EInvalidSecret)]
+fun test_shield_transfer_wrong_secret() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    // Sender initiates
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let config = ts::take_shared<ProtocolConfig>(&scenario);
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 1000);
+
+        let payment = mint_sui(100 * MIST_PER_SUI, ts::ctx(&mut scenario));
+        let secret_hash = sui::hash::keccak256(&b"correct-secret");
+
+        apex_payments::initiate_shield_transfer(
+            &config,
+            RECIPIENT,
+            payment,
+            86400_000,
+            secret_hash,
+            true, // require_secret
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        clock::destroy_for_testing(clock);
+        ts::return_shared(config);
+    };
+
+    // Try to claim with wrong secret
+    ts::next_tx(&mut scenario, RECIPIENT);
+    {
+        let session = ts::take_shared<ShieldSession>(&scenario);
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 5000);
+
+        apex_payments::complete_shield_transfer(
+            session,
+            b"wrong-secret", // Wrong!
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        clock::destroy_for_testing(clock);
+    };
+
+    ts::end(scenario);
+}
+
+// ==================== Trading Intent Tests ====================
+
+#[test]
+fun test_create_and_fill_intent() {
+    let mut scenario = ts::begin(ADMIN);
+
+    // Agent creates swap intent
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 1000);
+
+        let input_coin = mint_sui(1 * MIST_PER_SUI, ts::ctx(&mut scenario));
+
+        apex_trading::create_swap_intent<SUI>(
+            input_coin,
+            2_000_000, // min 2 USDC output (simulated)
+            AGENT,
+            3600_000, // 1 hour
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        clock::destroy_for_testing(clock);
+    };
+
+    // Verify intent was created
+    ts::next_tx(&mut scenario, EXECUTOR);
+    {
+        let intent = ts::take_shared<SwapIntent<SUI>>(&scenario);
+        assert!(apex_trading::intent_escrowed_amount(&intent) == 1 * MIST_PER_SUI, 0);
+        assert!(apex_trading::intent_min_output(&intent) == 2_000_000, 1);
+        assert!(!apex_trading::intent_is_filled(&intent), 2);
+        ts::return_shared(intent);
+    };
+
+    // Executor fills intent
+    ts::next_tx(&mut scenario, EXECUTOR);
+    {
+        let mut intent = ts::take_shared<SwapIntent<SUI>>(&scenario);
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 2000);
+
+        // Executor provides output (simulating DeepBook swap result)
+        let output_coin = mint_sui(3_000_000, ts::ctx(&mut scenario)); // More than min output
+
+        let (escrowed_input, receipt) = apex_trading::fill_intent<SUI, SUI>(
+            &mut intent,
+            output_coin,
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        // Verify executor received escrowed input
+        assert!(coin::value(&escrowed_input) == 1 * MIST_PER_SUI, 0);
+
+        // Verify intent marked as filled
+        assert!(apex_trading::intent_is_filled(&intent), 1);
+
+        transfer::public_transfer(escrowed_input, EXECUTOR);
+        transfer::public_transfer(receipt, EXECUTOR);
+        clock::destroy_for_testing(clock);
+        ts::return_shared(intent);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+#[expected_failure(abort_code = apex_trading::EInsufficientOutput)]
+fun test_fill_intent_insufficient_output() {
+    let mut scenario = ts::begin(ADMIN);
+
+    // Agent creates intent
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 1000);
+
+        apex_trading::create_swap_intent<SUI>(
+            mint_sui(1 * MIST_PER_SUI, ts::ctx(&mut scenario)),
+            2_000_000, // min 2 million mist
+            AGENT,
+            3600_000,
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        clock::destroy_for_testing(clock);
+    };
+
+    // Try to fill with insufficient output
+    ts::next_tx(&mut scenario, EXECUTOR);
+    {
+        let mut intent = ts::take_shared<SwapIntent<SUI>>(&scenario);
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 2000);
+
+        // Only 1 million - below 2 million minimum
+        let insufficient_output = mint_sui(1_000_000, ts::ctx(&mut scenario));
+
+        let (input, receipt) = apex_trading::fill_intent<SUI, SUI>(
+            &mut intent,
+            insufficient_output,
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        transfer::public_transfer(input, EXECUTOR);
+        transfer::public_transfer(receipt, EXECUTOR);
+        clock::destroy_for_testing(clock);
+        ts::return_shared(intent);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+fun test_cancel_intent() {
+    let mut scenario = ts::begin(ADMIN);
+
+    // Agent creates intent
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 1000);
+
+        apex_trading::create_swap_intent<SUI>(
+            mint_sui(1 * MIST_PER_SUI, ts::ctx(&mut scenario)),
+            2_000_000,
+            AGENT,
+            3600_000,
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        clock::destroy_for_testing(clock);
+    };
+
+    // Agent cancels intent
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let intent = ts::take_shared<SwapIntent<SUI>>(&scenario);
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 2000);
+
+        let refund = apex_trading::cancel_intent(
+            intent,
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        // Verify full refund
+        assert!(coin::value(&refund) == 1 * MIST_PER_SUI, 0);
+
+        transfer::public_transfer(refund, AGENT);
+        clock::destroy_for_testing(clock);
+    };
+
+    ts::end(scenario);
+}
+
+// ==================== Gated Trading Tests ====================
+
+#[test]
+fun test_gated_trading_service() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    // Register APEX service
+    ts::next_tx(&mut scenario, PROVIDER);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        apex_payments::register_service(
+            &mut config,
+            b"Trading API",
+            b"Premium trading signals",
+            10_000_000,
+            mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
+            ts::ctx(&mut scenario)
+        );
+        ts::return_shared(config);
+    };
+
+    // Get service ID and create trading service
+    ts::next_tx(&mut scenario, PROVIDER);
+    {
+        let service = ts::take_shared<ServiceProvider>(&scenario);
+        let apex_service_id = object::id(&service);
+
+        apex_trading::create_and_share_trading_service(
+            apex_service_id,
+            5_000_000, // 0.005 SUI fee per trade
+            ts::ctx(&mut scenario)
+        );
+
+        ts::return_shared(service);
+    };
+
+    // Agent purchases access
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        let mut service = ts::take_shared<ServiceProvider>(&scenario);
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 1000);
+
+        let capability = apex_payments::purchase_access(
+            &mut config,
+            &mut service,
+            mint_sui(1 * MIST_PER_SUI, ts::ctx(&mut scenario)),
+            100,
+            3600_000,
+            0,
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        transfer::public_transfer(capability, AGENT);
+
+        clock::destroy_for_testing(clock);
+        ts::return_shared(service);
+        ts::return_shared(config);
+    };
+
+    // Agent verifies payment before trading
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let mut trading_service = ts::take_shared<TradingService>(&scenario);
+        let apex_service = ts::take_shared<ServiceProvider>(&scenario);
+        let mut capability = ts::take_from_sender<AccessCapability>(&scenario);
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 2000);
+
+        let verified = apex_trading::verify_trade_payment(
+            &mut trading_service,
+            &apex_service,
+            &mut capability,
+            1, // consume 1 unit
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        assert!(verified, 0);
+        assert!(apex_payments::capability_remaining(&capability) == 99, 1);
+        assert!(apex_trading::trading_service_total_trades(&trading_service) == 1, 2);
+
+        clock::destroy_for_testing(clock);
+        ts::return_to_sender(&scenario, capability);
+        ts::return_shared(apex_service);
+        ts::return_shared(trading_service);
+    };
+
+    ts::end(scenario);
+}
+
+// ==================== Atomic PTB Pattern Tests ====================
+// These tests demonstrate multi-operation sequences that mirror PTB behavior
+
+#[test]
+fun test_atomic_purchase_and_use() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    // Register service
+    ts::next_tx(&mut scenario, PROVIDER);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        apex_payments::register_service(
+            &mut config,
+            b"API",
+            b"Test",
+            10_000_000,
+            mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
+            ts::ctx(&mut scenario)
+        );
+        ts::return_shared(config);
+    };
+
+    // Simulate PTB: purchase access + use access in same transaction
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        let mut service = ts::take_shared<ServiceProvider>(&scenario);
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 1000);
+
+        // Command 1: Purchase access
+        let mut capability = apex_payments::purchase_access(
+            &mut config,
+            &mut service,
+            mint_sui(1 * MIST_PER_SUI, ts::ctx(&mut scenario)),
+            100,
+            3600_000,
+            0,
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        // Command 2: Use access (in same tx, capability passed between commands)
+        let success = apex_payments::use_access(
+            &mut capability,
+            &service,
+            5,
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        assert!(success, 0);
+        assert!(apex_payments::capability_remaining(&capability) == 95, 1);
+
+        // Command 3: Transfer to recipient
+        transfer::public_transfer(capability, AGENT);
+
+        clock::destroy_for_testing(clock);
+        ts::return_shared(service);
+        ts::return_shared(config);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+fun test_protocol_pause() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    // Admin pauses protocol
+    ts::next_tx(&mut scenario, ADMIN);
+    {
+        let admin_cap = ts::take_from_sender<AdminCap>(&scenario);
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+
+        apex_payments::set_protocol_paused(&admin_cap, &mut config, true);
+        assert!(apex_payments::protocol_is_paused(&config), 0);
+
+        ts::return_to_sender(&scenario, admin_cap);
+        ts::return_shared(config);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+#[expected_failure(abort_code = apex_payments::EProtocolPaused)]
+fun test_register_while_paused() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    // Admin pauses protocol
+    ts::next_tx(&mut scenario, ADMIN);
+    {
+        let admin_cap = ts::take_from_sender<AdminCap>(&scenario);
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+
+        apex_payments::set_protocol_paused(&admin_cap, &mut config, true);
+
+        ts::return_to_sender(&scenario, admin_cap);
+        ts::return_shared(config);
+    };
+
+    // Try to register while paused
+    ts::next_tx(&mut scenario, PROVIDER);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+
+        apex_payments::register_service(
+            &mut config,
+            b"Test",
+            b"Test",
+            10_000_000,
+            mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
+            ts::ctx(&mut scenario)
+        );
+
+        ts::return_shared(config);
+    };
+
+    ts::end(scenario);
+}
+
+/// Admin lowers the per-provider cap, then PROVIDER registers `count` services
+fun register_services_with_cap(scenario: &mut Scenario, cap: u64, count: u64) {
+    ts::next_tx(scenario, ADMIN);
+    {
+        let admin_cap = ts::take_from_sender<AdminCap>(scenario);
+        let mut config = ts::take_shared<ProtocolConfig>(scenario);
+
+        apex_payments::set_max_services_per_provider(&admin_cap, &mut config, cap);
+        assert!(apex_payments::max_services_per_provider(&config) == cap, 0);
+
+        ts::return_to_sender(scenario, admin_cap);
+        ts::return_shared(config);
+    };
+
+    let mut i = 0;
+    while (i < count) {
+        ts::next_tx(scenario, PROVIDER);
+        {
+            let mut config = ts::take_shared<ProtocolConfig>(scenario);
+
+            apex_payments::register_service(
+                &mut config,
+                b"Test",
+                b"Test",
+                10_000_000,
+                mint_sui(REGISTRATION_FEE, ts::ctx(scenario)),
+                ts::ctx(scenario)
+            );
+
+            ts::return_shared(config);
+        };
+        i = i + 1;
+    };
+}
+
+#[test]
+fun test_register_services_up_to_provider_cap() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    register_services_with_cap(&mut scenario, 2, 2);
+
+    ts::next_tx(&mut scenario, PROVIDER);
+    {
+        let config = ts::take_shared<ProtocolConfig>(&scenario);
+        assert!(apex_payments::provider_service_count(&config, PROVIDER) == 2, 0);
+        // Other providers are counted separately
+        assert!(apex_payments::provider_service_count(&config, AGENT) == 0, 1);
+        ts::return_shared(config);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+fun test_services_of_lists_provider_services() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    register_services_with_cap(&mut scenario, 3, 3);
+
+    ts::next_tx(&mut scenario, PROVIDER);
+    {
+        let config = ts::take_shared<ProtocolConfig>(&scenario);
+        let services = apex_payments::services_of(&config, PROVIDER);
+        assert!(services.length() == 3, 0);
+        assert!(apex_payments::services_of(&config, AGENT).is_empty(), 1);
+
+        // Every listed ID is a live, distinct ServiceProvider
+        let mut i = 0;
+        while (i < 3) {
+            let service = ts::take_shared_by_id<ServiceProvider>(&scenario, services[i]);
+            assert!(apex_payments::service_price(&service) == 10_000_000, 2);
+            ts::return_shared(service);
+            i = i + 1;
+        };
+        assert!(services[0] != services[1] && services[1] != services[2] && services[0] != services[2], 3);
+
+        ts::return_shared(config);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+#[expected_failure(abort_code = apex_payments::EServiceLimitReached)]
+fun test_register_service_past_provider_cap() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    register_services_with_cap(&mut scenario, 2, 3);
+
+    ts::end(scenario);
+}
+
+// ==================== Agent Authorization Tests ====================
+
+#[test]
+fun test_create_authorization() {
+    let mut scenario = ts::begin(OWNER);
+
+    // Owner creates authorization for agent
+    ts::next_tx(&mut scenario, OWNER);
+    {
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 1000);
+
+        let auth = apex_payments::create_authorization(
+            AGENT, // authorized agent
+            vector::empty(), // all services allowed
+            100_000_000, // 0.1 SUI spend limit per tx
+            1_000_000_000, // 1 SUI daily limit
+            86400_000, // 24 hour duration
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        // Verify authorization
+        assert!(apex_payments::authorization_owner(&auth) == OWNER, 0);
+        assert!(apex_payments::authorization_agent(&auth) == AGENT, 1);
+        assert!(!apex_payments::authorization_is_paused(&auth), 2);
+
+        transfer::public_transfer(auth, OWNER);
+        clock::destroy_for_testing(clock);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+#[expected_failure(abort_code = apex_payments::EInconsistentLimits)]
+fun test_create_authorization_per_tx_above_daily() {
+    let mut scenario = ts::begin(OWNER);
+
+    ts::next_tx(&mut scenario, OWNER);
+    {
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+
+        // 2 SUI per tx can never fit inside a 1 SUI daily limit
+        let auth = apex_payments::create_authorization(
+            AGENT,
+            vector::empty(),
+            2_000_000_000,
+            1_000_000_000,
+            0,
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        transfer::public_transfer(auth, OWNER);
+        clock::destroy_for_testing(clock);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+fun test_create_authorization_per_tx_equal_daily() {
+    let mut scenario = ts::begin(OWNER);
+
+    ts::next_tx(&mut scenario, OWNER);
+    {
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+
+        let auth = apex_payments::create_authorization(
+            AGENT,
+            vector::empty(),
+            1_000_000_000,
+            1_000_000_000,
+            0,
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+        assert!(apex_payments::authorization_daily_remaining(&auth) == 1_000_000_000, 0);
+
+        transfer::public_transfer(auth, OWNER);
+        clock::destroy_for_testing(clock);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+fun test_authorized_purchase() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    // Register service
+    ts::next_tx(&mut scenario, PROVIDER);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        apex_payments::register_service(
+            &mut config,
+            b"API Service",
+            b"Test service",
+            10_000_000, // 0.01 SUI per unit
+            mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
+            ts::ctx(&mut scenario)
+        );
+        ts::return_shared(config);
+    };
+
+    // Owner creates authorization for agent
+    ts::next_tx(&mut scenario, OWNER);
+    {
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 1000);
+
+        let auth = apex_payments::create_authorization(
+            AGENT,
+            vector::empty(), // all services allowed
+            500_000_000, // 0.5 SUI spend limit per tx
+            1_000_000_000, // 1 SUI daily limit
+            86400_000, // 24 hours
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        transfer::public_transfer(auth, AGENT);
+        clock::destroy_for_testing(clock);
+    };
+
+    // Agent uses authorization to purchase access
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        let mut service = ts::take_shared<ServiceProvider>(&scenario);
+        let mut auth = ts::take_from_sender<AgentAuthorization>(&scenario);
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 2000);
+
+        let payment = mint_sui(100_000_000, ts::ctx(&mut scenario)); // 0.1 SUI for 10 units
+
+        let capability = apex_payments::authorized_purchase(
+            &mut auth,
+            &mut config,
+            &mut service,
+            payment,
+            10, // 10 units
+            3600_000, // 1 hour
+            0, // no rate limit
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        assert!(apex_payments::capability_remaining(&capability) == 10, 0);
+
+        transfer::public_transfer(capability, AGENT);
+        clock::destroy_for_testing(clock);
+        ts::return_to_sender(&scenario, auth);
+        ts::return_shared(service);
+        ts::return_shared(config);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+#[expected_failure(abort_code = apex_payments::EExceededLimit)]
+fun test_authorized_purchase_exceeds_limit() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    // Register service
+    ts::next_tx(&mut scenario, PROVIDER);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        apex_payments::register_service(
+            &mut config,
+            b"API",
+            b"Test",
+            10_000_000,
+            mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
+            ts::ctx(&mut scenario)
+        );
+        ts::return_shared(config);
+    };
+
+    // Owner creates authorization with low spend limit
+    ts::next_tx(&mut scenario, OWNER);
+    {
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 1000);
+
+        let auth = apex_payments::create_authorization(
+            AGENT,
+            vector::empty(),
+            50_000_000, // Only 0.05 SUI spend limit per tx
+            1_000_000_000,
+            86400_000,
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        transfer::public_transfer(auth, AGENT);
+        clock::destroy_for_testing(clock);
+    };
+
+    // Agent tries to purchase more than limit
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        let mut service = ts::take_shared<ServiceProvider>(&scenario);
+        let mut auth = ts::take_from_sender<AgentAuthorization>(&scenario);
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 2000);
+
+        // Try to pay 0.1 SUI (exceeds 0.05 limit)
+        let payment = mint_sui(100_000_000, ts::ctx(&mut scenario));
+
+        let capability = apex_payments::authorized_purchase(
+            &mut auth,
+            &mut config,
+            &mut service,
+            payment,
+            10, // 10 units = 0.1 SUI
+            3600_000,
+            0,
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        transfer::public_transfer(capability, AGENT);
+        clock::destroy_for_testing(clock);
+        ts::return_to_sender(&scenario, auth);
+        ts::return_shared(service);
+        ts::return_shared(config);
+    };
+
+    ts::end(scenario);
+}
+
+/// Agent buys 10 units (0.1 SUI) under its authorization
+fun authorized_purchase_of_ten(scenario: &mut Scenario) {
+    ts::next_tx(scenario, AGENT);
+    let mut config = ts::take_shared<ProtocolConfig>(scenario);
+    let mut service = ts::take_shared<ServiceProvider>(scenario);
+    let mut auth = ts::take_from_sender<AgentAuthorization>(scenario);
+    let clock = clock::create_for_testing(ts::ctx(scenario));
+
+    let capability = apex_payments::authorized_purchase(
+        &mut auth,
+        &mut config,
+        &mut service,
+        mint_sui(100_000_000, ts::ctx(scenario)),
+        10,
+        3600_000,
+        0,
+        &clock,
+        ts::ctx(scenario)
+    );
+
+    transfer::public_transfer(capability, AGENT);
+    clock::destroy_for_testing(clock);
+    ts::return_to_sender(scenario, auth);
+    ts::return_shared(service);
+    ts::return_shared(config);
+}
+
+#[test]
+fun test_increase_daily_limit_after_hitting_it() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    ts::next_tx(&mut scenario, PROVIDER);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        apex_payments::register_service(
+            &mut config,
+            b"API",
+            b"Test",
+            10_000_000,
+            mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
+            ts::ctx(&mut scenario)
+        );
+        ts::return_shared(config);
+    };
+
+    // 0.2 SUI a day, 0.1 SUI per purchase
+    ts::next_tx(&mut scenario, OWNER);
+    {
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        let auth = apex_payments::create_authorization(
+            AGENT,
+            vector::empty(),
+            100_000_000,
+            200_000_000,
+            0,
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+        transfer::public_transfer(auth, AGENT);
+        clock::destroy_for_testing(clock);
+    };
+
+    authorized_purchase_of_ten(&mut scenario);
+    authorized_purchase_of_ten(&mut scenario);
+
+    // Limit reached; owner tops it up by another 0.1 SUI
+    ts::next_tx(&mut scenario, OWNER);
+    {
+        let mut auth = ts::take_from_address<AgentAuthorization>(&scenario, AGENT);
+        assert!(apex_payments::authorization_daily_remaining(&auth) == 0, 0);
+
+        apex_payments::increase_daily_limit(&mut auth, 100_000_000, ts::ctx(&mut scenario));
+        assert!(apex_payments::authorization_daily_remaining(&auth) == 100_000_000, 1);
+
+        ts::return_to_address(AGENT, auth);
+    };
+
+    // Third purchase fits inside the raised limit and uses it up
+    authorized_purchase_of_ten(&mut scenario);
+
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let auth = ts::take_from_sender<AgentAuthorization>(&scenario);
+        assert!(apex_payments::authorization_daily_remaining(&auth) == 0, 2);
+        ts::return_to_sender(&scenario, auth);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+#[expected_failure(abort_code = apex_payments::EUnauthorized)]
+fun test_increase_daily_limit_by_agent() {
+    let mut scenario = ts::begin(OWNER);
+
+    ts::next_tx(&mut scenario, OWNER);
+    {
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        let auth = apex_payments::create_authorization(
+            AGENT,
+            vector::empty(),
+            100_000_000,
+            200_000_000,
+            0,
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+        transfer::public_transfer(auth, AGENT);
+        clock::destroy_for_testing(clock);
+    };
+
+    // Only the owner may raise the budget
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let mut auth = ts::take_from_sender<AgentAuthorization>(&scenario);
+        apex_payments::increase_daily_limit(&mut auth, 100_000_000, ts::ctx(&mut scenario));
+        ts::return_to_sender(&scenario, auth);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+fun test_pause_and_revoke_authorization() {
+    let mut scenario = ts::begin(OWNER);
+
+    // Owner creates authorization
+    ts::next_tx(&mut scenario, OWNER);
+    {
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 1000);
+
+        let auth = apex_payments::create_authorization(
+            AGENT,
+            vector::empty(),
+            100_000_000,
+            1_000_000_000,
+            86400_000,
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        transfer::public_transfer(auth, OWNER);
+        clock::destroy_for_testing(clock);
+    };
+
+    // Owner pauses authorization
+    ts::next_tx(&mut scenario, OWNER);
+    {
+        let mut auth = ts::take_from_sender<AgentAuthorization>(&scenario);
+
+        apex_payments::pause_authorization(&mut auth, ts::ctx(&mut scenario));
+        assert!(apex_payments::authorization_is_paused(&auth), 0);
+
+        // Owner unpauses
+        apex_payments::unpause_authorization(&mut auth, ts::ctx(&mut scenario));
+        assert!(!apex_payments::authorization_is_paused(&auth), 1);
+
+        ts::return_to_sender(&scenario, auth);
+    };
+
+    // Owner revokes authorization
+    ts::next_tx(&mut scenario, OWNER);
+    {
+        let auth = ts::take_from_sender<AgentAuthorization>(&scenario);
+        apex_payments::revoke_authorization(auth, ts::ctx(&mut scenario));
+    };
+
+    ts::end(scenario);
+}
+
+// ==================== Trusted Meter Tests ====================
+
+#[test]
+fun test_register_meter() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    // Admin registers a trusted meter
+    ts::next_tx(&mut scenario, ADMIN);
+    {
+        let admin_cap = ts::take_from_sender<AdminCap>(&scenario);
+
+        // 32-byte Ed25519 pubkey (simulated)
+        let enclave_pubkey = x"0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+        let pcr_values = b"pcr0:abc123";
+
+        let meter = apex_payments::register_meter(
+            &admin_cap,
+            enclave_pubkey,
+            pcr_values,
+            b"Test Nautilus Meter",
+            ts::ctx(&mut scenario)
+        );
+
+        assert!(apex_payments::meter_is_active(&meter), 0);
+        assert!(apex_payments::meter_pubkey(&meter) == enclave_pubkey, 1);
+
+        transfer::public_transfer(meter, ADMIN);
+        ts::return_to_sender(&scenario, admin_cap);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+fun test_deactivate_meter() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    // Register meter
+    ts::next_tx(&mut scenario, ADMIN);
+    {
+        let admin_cap = ts::take_from_sender<AdminCap>(&scenario);
+        let enclave_pubkey = x"0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+        let meter = apex_payments::register_meter(
+            &admin_cap,
+            enclave_pubkey,
+            b"pcr0:abc",
+            b"Test Meter",
+            ts::ctx(&mut scenario)
+        );
+
+        transfer::public_transfer(meter, ADMIN);
+        ts::return_to_sender(&scenario, admin_cap);
+    };
+
+    // Deactivate meter
+    ts::next_tx(&mut scenario, ADMIN);
+    {
+        let admin_cap = ts::take_from_sender<AdminCap>(&scenario);
+        let mut meter = ts::take_from_sender<TrustedMeter>(&scenario);
+
+        apex_payments::deactivate_meter(&admin_cap, &mut meter);
+        assert!(!apex_payments::meter_is_active(&meter), 0);
+
+        ts::return_to_sender(&scenario, meter);
+        ts::return_to_sender(&scenario, admin_cap);
+    };
+
+    ts::end(scenario);
+}
+
+// ==================== Service Registry Tests ====================
+
+#[test]
+fun test_create_registry() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    // Admin creates registry
+    ts::next_tx(&mut scenario, ADMIN);
+    {
+        let admin_cap = ts::take_from_sender<AdminCap>(&scenario);
+
+        apex_payments::create_registry(&admin_cap, ts::ctx(&mut scenario));
+
+        ts::return_to_sender(&scenario, admin_cap);
+    };
+
+    // Verify registry was created
+    ts::next_tx(&mut scenario, ADMIN);
+    {
+        let registry = ts::take_shared<ServiceRegistry>(&scenario);
+        assert!(apex_payments::registry_count(&registry) == 0, 0);
+        ts::return_shared(registry);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+fun test_list_and_delist_service() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    // Register a service
+    ts::next_tx(&mut scenario, PROVIDER);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        apex_payments::register_service(
+            &mut config,
+            b"Oracle Service",
+            b"Price feed oracle",
+            5_000_000,
+            mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
+            ts::ctx(&mut scenario)
+        );
+        ts::return_shared(config);
+    };
+
+    // Admin creates registry
+    ts::next_tx(&mut scenario, ADMIN);
+    {
+        let admin_cap = ts::take_from_sender<AdminCap>(&scenario);
+        apex_payments::create_registry(&admin_cap, ts::ctx(&mut scenario));
+        ts::return_to_sender(&scenario, admin_cap);
+    };
+
+    // Provider lists service in registry
+    ts::next_tx(&mut scenario, PROVIDER);
+    {
+        let service = ts::take_shared<ServiceProvider>(&scenario);
+        let mut registry = ts::take_shared<ServiceRegistry>(&scenario);
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 1000);
+
+        apex_payments::list_service(
+            &mut registry,
+            &service,
+            b"oracle",
+            b"walrus_blob_id_123",
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        assert!(apex_payments::registry_count(&registry) == 1, 0);
+
+        clock::destroy_for_testing(clock);
+        ts::return_shared(registry);
+        ts::return_shared(service);
+    };
+
+    // Provider delists service
+    ts::next_tx(&mut scenario, PROVIDER);
+    {
+        let service = ts::take_shared<ServiceProvider>(&scenario);
+        let mut registry = ts::take_shared<ServiceRegistry>(&scenario);
+
+        apex_payments::delist_service(
+            &mut registry,
+            &service,
+            ts::ctx(&mut scenario)
+        );
+
+        assert!(apex_payments::registry_count(&registry) == 0, 0);
+
+        ts::return_shared(registry);
+        ts::return_shared(service);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+fun test_new_service_listed_before_share() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    ts::next_tx(&mut scenario, ADMIN);
+    {
+        let admin_cap = ts::take_from_sender<AdminCap>(&scenario);
+        apex_payments::create_registry(&admin_cap, ts::ctx(&mut scenario));
+        ts::return_to_sender(&scenario, admin_cap);
+    };
+
+    // Register, list and share in one transaction
+    ts::next_tx(&mut scenario, PROVIDER);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        let mut registry = ts::take_shared<ServiceRegistry>(&scenario);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+
+        let service = apex_payments::new_service(
+            &mut config,
+            b"Oracle Service",
+            b"Price feed oracle",
+            5_000_000,
+            mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
+            ts::ctx(&mut scenario)
+        );
+        apex_payments::list_service(
+            &mut registry,
+            &service,
+            b"oracle",
+            b"walrus_blob_id_123",
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+        apex_payments::share_service(service);
+
+        assert!(apex_payments::registry_count(&registry) == 1, 0);
+
+        clock::destroy_for_testing(clock);
+        ts::return_shared(registry);
+        ts::return_shared(config);
+    };
+
+    ts::next_tx(&mut scenario, PROVIDER);
+    {
+        let config = ts::take_shared<ProtocolConfig>(&scenario);
+        let service = ts::take_shared<ServiceProvider>(&scenario);
+        assert!(apex_payments::services_of(&config, PROVIDER) == vector[object::id(&service)], 1);
+        ts::return_shared(service);
+        ts::return_shared(config);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+#[expected_failure(abort_code = apex_payments::EUnauthorized)]
+fun test_list_service_by_non_provider() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    ts::next_tx(&mut scenario, PROVIDER);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        apex_payments::register_service(
+            &mut config,
+            b"Oracle Service",
+            b"Price feed oracle",
+            5_000_000,
+            mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
+            ts::ctx(&mut scenario)
+        );
+        ts::return_shared(config);
+    };
+
+    ts::next_tx(&mut scenario, ADMIN);
+    {
+        let admin_cap = ts::take_from_sender<AdminCap>(&scenario);
+        apex_payments::create_registry(&admin_cap, ts::ctx(&mut scenario));
+        ts::return_to_sender(&scenario, admin_cap);
+    };
+
+    // Third party tries to list the provider's service under its own category
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let service = ts::take_shared<ServiceProvider>(&scenario);
+        let mut registry = ts::take_shared<ServiceRegistry>(&scenario);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+
+        apex_payments::list_service(
+            &mut registry,
+            &service,
+            b"scam",
+            b"blob_id",
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        clock::destroy_for_testing(clock);
+        ts::return_shared(registry);
+        ts::return_shared(service);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+fun test_set_featured_service() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    // Register a service
+    ts::next_tx(&mut scenario, PROVIDER);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        apex_payments::register_service(
+            &mut config,
+            b"Featured Service",
+            b"A great service",
+            10_000_000,
+            mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
+            ts::ctx(&mut scenario)
+        );
+        ts::return_shared(config);
+    };
+
+    // Admin creates registry
+    ts::next_tx(&mut scenario, ADMIN);
+    {
+        let admin_cap = ts::take_from_sender<AdminCap>(&scenario);
+        apex_payments::create_registry(&admin_cap, ts::ctx(&mut scenario));
+        ts::return_to_sender(&scenario, admin_cap);
+    };
+
+    // Provider lists service
+    ts::next_tx(&mut scenario, PROVIDER);
+    {
+        let service = ts::take_shared<ServiceProvider>(&scenario);
+        let mut registry = ts::take_shared<ServiceRegistry>(&scenario);
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 1000);
+
+        apex_payments::list_service(
+            &mut registry,
+            &service,
+            b"defi",
+            b"blob_id",
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        clock::destroy_for_testing(clock);
+        ts::return_shared(registry);
+        ts::return_shared(service);
+    };
+
+    // Admin sets featured status
+    ts::next_tx(&mut scenario, ADMIN);
+    {
+        let mut registry = ts::take_shared<ServiceRegistry>(&scenario);
+        let service = ts::take_shared<ServiceProvider>(&scenario);
+        let service_id = object::id(&service);
+
+        apex_payments::set_featured(
+            &mut registry,
+            service_id,
+            true,
+            ts::ctx(&mut scenario)
+        );
+
+        // Verify featured status via registry_get
+        let (id, _name, _category, _price, featured) = apex_payments::registry_get(&registry, 0);
+        assert!(id == service_id, 0);
+        assert!(featured == true, 1);
+
+        ts::return_shared(service);
+        ts::return_shared(registry);
+    };
+
+    ts::end(scenario);
+}
+
+// ==================== Seal Tests ====================
+
+#[test]
+fun test_seal_approve_does_not_mutate() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    ts::next_tx(&mut scenario, ADMIN);
+    {
+        apex_seal::init_for_testing(ts::ctx(&mut scenario));
+        let mut clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        clock::set_for_testing(&mut clock, 1000);
+        clock::share_for_testing(clock);
+    };
+
+    ts::next_tx(&mut scenario, PROVIDER);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        apex_payments::register_service(
+            &mut config,
+            b"Encrypted Data",
+            b"Seal-gated content",
+            10_000_000,
+            mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
+            ts::ctx(&mut scenario)
+        );
+        ts::return_shared(config);
+    };
+
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        let mut service = ts::take_shared<ServiceProvider>(&scenario);
+        let clock = ts::take_shared<clock::Clock>(&scenario);
+
+        let capability = apex_payments::purchase_access(
+            &mut config,
+            &mut service,
+            mint_sui(1 * MIST_PER_SUI, ts::ctx(&mut scenario)),
+            10,
+            3600_000,
+            0,
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+        transfer::public_transfer(capability, AGENT);
+
+        ts::return_shared(clock);
+        ts::return_shared(service);
+        ts::return_shared(config);
+    };
+
+    // Key-server style approval: reads only
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let pkg_version = ts::take_shared<PackageVersion>(&scenario);
+        let service = ts::take_shared<ServiceProvider>(&scenario);
+        let capability = ts::take_from_sender<AccessCapability>(&scenario);
+        let clock = ts::take_shared<clock::Clock>(&scenario);
+        let served_before = apex_payments::service_total_served(&service);
+
+        let content_id = apex_seal::create_content_id(&service, b"doc-1");
+        apex_seal::seal_approve_for_testing(content_id, &pkg_version, &capability, &service, &clock);
+
+        assert!(apex_payments::capability_remaining(&capability) == 10, 0);
+        assert!(apex_payments::service_total_served(&service) == served_before, 1);
+
+        ts::return_shared(clock);
+        ts::return_to_sender(&scenario, capability);
+        ts::return_shared(service);
+        ts::return_shared(pkg_version);
+    };
+
+    // The approval transaction created nothing and emitted nothing
+    let effects = ts::next_tx(&mut scenario, AGENT);
+    assert!(ts::created(&effects).is_empty(), 2);
+    assert!(ts::num_user_events(&effects) == 0, 3);
+
+    ts::end(scenario);
+}
+
+// ==================== Hedge Fund Tests ====================
+
+/// Create a fund (OWNER), have AGENT join with 10 SUI, then trade and settle
+fun setup_settled_fund(scenario: &mut Scenario, fees_after_investors: bool) {
+    setup_protocol(scenario);
+
+    ts::next_tx(scenario, PROVIDER);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(scenario);
+        apex_payments::register_service(
+            &mut config,
+            b"Fund Entry",
+            b"Entry fee collection",
+            100_000_000,
+            mint_sui(REGISTRATION_FEE, ts::ctx(scenario)),
+            ts::ctx(scenario)
+        );
+        ts::return_shared(config);
+    };
+
+    ts::next_tx(scenario, OWNER);
+    {
+        let config = ts::take_shared<ProtocolConfig>(scenario);
+        let mut service = ts::take_shared<ServiceProvider>(scenario);
+        let clock = clock::create_for_testing(ts::ctx(scenario));
+
+        apex_fund::create_fund(
+            &config,
+            &mut service,
+            b"Test Fund",
+            100_000_000,
+            200,
+            2000,
+            1000 * MIST_PER_SUI,
+            mint_sui(1 * MIST_PER_SUI, ts::ctx(scenario)),
+            &clock,
+            ts::ctx(scenario)
+        );
+
+        clock::destroy_for_testing(clock);
+        ts::return_shared(service);
+        ts::return_shared(config);
+    };
+
+    if (fees_after_investors) {
+        ts::next_tx(scenario, OWNER);
+        let mut fund = ts::take_shared<HedgeFund>(scenario);
+        apex_fund::set_fees_after_investors(&mut fund, true, ts::ctx(scenario));
+        ts::return_shared(fund);
+    };
+
+    ts::next_tx(scenario, AGENT);
+    {
+        let mut fund = ts::take_shared<HedgeFund>(scenario);
+        let mut config = ts::take_shared<ProtocolConfig>(scenario);
+        let mut service = ts::take_shared<ServiceProvider>(scenario);
+        let clock = clock::create_for_testing(ts::ctx(scenario));
+
+        let position = apex_fund::join_fund(
+            &mut fund,
+            &mut config,
+            &mut service,
+            mint_sui(100_000_000, ts::ctx(scenario)),
+            mint_sui(10 * MIST_PER_SUI, ts::ctx(scenario)),
+            &clock,
+            ts::ctx(scenario)
+        );
+        transfer::public_transfer(position, AGENT);
+
+        clock::destroy_for_testing(clock);
+        ts::return_shared(service);
+        ts::return_shared(config);
+        ts::return_shared(fund);
+    };
+
+    ts::next_tx(scenario, OWNER);
+    {
+        let mut fund = ts::take_shared<HedgeFund>(scenario);
+        let clock = clock::create_for_testing(ts::ctx(scenario));
+
+        apex_fund::start_trading(&mut fund, &clock, ts::ctx(scenario));
+        apex_fund::settle_fund(&mut fund, &clock, ts::ctx(scenario));
+
+        clock::destroy_for_testing(clock);
+        ts::return_shared(fund);
+    };
+}
+
+#[test]
+fun test_join_fund_shared_position_withdraws() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_protocol(&mut scenario);
+
+    ts::next_tx(&mut scenario, PROVIDER);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        apex_payments::register_service(
+            &mut config,
+            b"Fund Entry",
+            b"Entry fee collection",
+            100_000_000,
+            mint_sui(REGISTRATION_FEE, ts::ctx(&mut scenario)),
+            ts::ctx(&mut scenario)
+        );
+        ts::return_shared(config);
+    };
+
+    ts::next_tx(&mut scenario, OWNER);
+    {
+        let config = ts::take_shared<ProtocolConfig>(&scenario);
+        let mut service = ts::take_shared<ServiceProvider>(&scenario);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+
+        apex_fund::create_fund(
+            &config,
+            &mut service,
+            b"Test Fund",
+            100_000_000,
+            200,
+            2000,
+            1000 * MIST_PER_SUI,
+            mint_sui(1 * MIST_PER_SUI, ts::ctx(&mut scenario)),
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        clock::destroy_for_testing(clock);
+        ts::return_shared(service);
+        ts::return_shared(config);
+    };
+
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let mut fund = ts::take_shared<HedgeFund>(&scenario);
+        let mut config = ts::take_shared<ProtocolConfig>(&scenario);
+        let mut service = ts::take_shared<ServiceProvider>(&scenario);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+
+        apex_fund::join_fund_shared(
+            &mut fund,
+            &mut config,
+            &mut service,
+            mint_sui(100_000_000, ts::ctx(&mut scenario)),
+            mint_sui(10 * MIST_PER_SUI, ts::ctx(&mut scenario)),
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+
+        clock::destroy_for_testing(clock);
+        ts::return_shared(service);
+        ts::return_shared(config);
+        ts::return_shared(fund);
+    };
+
+    ts::next_tx(&mut scenario, OWNER);
+    {
+        let mut fund = ts::take_shared<HedgeFund>(&scenario);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+
+        apex_fund::start_trading(&mut fund, &clock, ts::ctx(&mut scenario));
+        apex_fund::settle_fund(&mut fund, &clock, ts::ctx(&mut scenario));
+
+        clock::destroy_for_testing(clock);
+        ts::return_shared(fund);
+    };
+
+    // The shared position is fetched by type, not from the investor's inventory
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let mut fund = ts::take_shared<HedgeFund>(&scenario);
+        let position = ts::take_shared<InvestorPosition>(&scenario);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        assert!(apex_fund::position_investor(&position) == AGENT, 0);
+
+        let receipt = apex_fund::withdraw_shares(&mut fund, position, &clock, ts::ctx(&mut scenario));
+        transfer::public_transfer(receipt, AGENT);
+        assert!(apex_fund::fund_outstanding_positions(&fund) == 0, 1);
+
+        clock::destroy_for_testing(clock);
+        ts::return_shared(fund);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+#[expected_failure(abort_code = apex_fund::EInvestorsNotRedeemed)]
+fun test_fees_after_investors_blocks_early_withdrawal() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_settled_fund(&mut scenario, true);
+
+    // Investor has not redeemed yet
+    ts::next_tx(&mut scenario, OWNER);
+    {
+        let mut fund = ts::take_shared<HedgeFund>(&scenario);
+        let fees = apex_fund::withdraw_manager_fees(&mut fund, ts::ctx(&mut scenario));
+        transfer::public_transfer(fees, OWNER);
+        ts::return_shared(fund);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+fun test_fees_after_investors_allows_withdrawal_once_redeemed() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_settled_fund(&mut scenario, true);
+
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let mut fund = ts::take_shared<HedgeFund>(&scenario);
+        let position = ts::take_from_sender<InvestorPosition>(&scenario);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        assert!(apex_fund::fund_outstanding_positions(&fund) == 1, 0);
+
+        let receipt = apex_fund::withdraw_shares(&mut fund, position, &clock, ts::ctx(&mut scenario));
+        transfer::public_transfer(receipt, AGENT);
+        assert!(apex_fund::fund_outstanding_positions(&fund) == 0, 1);
+
+        clock::destroy_for_testing(clock);
+        ts::return_shared(fund);
+    };
+
+    ts::next_tx(&mut scenario, OWNER);
+    {
+        let mut fund = ts::take_shared<HedgeFund>(&scenario);
+        let fees = apex_fund::withdraw_manager_fees(&mut fund, ts::ctx(&mut scenario));
+        assert!(coin::value(&fees) > 0, 2);
+        transfer::public_transfer(fees, OWNER);
+        ts::return_shared(fund);
+    };
+
+    ts::end(scenario);
+}
+
+/// Fund with a 0.1 SUI entry fee, 20% of which goes to the protocol, whose
+/// entry service is priced at `service_price`; AGENT joins it with exactly
+/// the entry fee. Returns the treasury growth from the join.
+fun join_split_fund(scenario: &mut Scenario, service_price: u64): u64 {
+    setup_protocol(scenario);
+
+    ts::next_tx(scenario, PROVIDER);
+    {
+        let mut config = ts::take_shared<ProtocolConfig>(scenario);
+        apex_payments::register_service(
+            &mut config,
+            b"Fund Entry",
+            b"Entry fee collection",
+            service_price,
+            mint_sui(REGISTRATION_FEE, ts::ctx(scenario)),
+            ts::ctx(scenario)
+        );
+        ts::return_shared(config);
+    };
+
+    ts::next_tx(scenario, OWNER);
+    {
+        let config = ts::take_shared<ProtocolConfig>(scenario);
+        let mut service = ts::take_shared<ServiceProvider>(scenario);
+        let clock = clock::create_for_testing(ts::ctx(scenario));
+
+        apex_fund::create_fund(
+            &config,
+            &mut service,
+            b"Split Fund",
+            100_000_000,
+            200,
+            2000,
+            1000 * MIST_PER_SUI,
+            mint_sui(1 * MIST_PER_SUI, ts::ctx(scenario)),
+            &clock,
+            ts::ctx(scenario)
+        );
+
+        clock::destroy_for_testing(clock);
+        ts::return_shared(service);
+        ts::return_shared(config);
+    };
+
+    // 20% of entry fees to the protocol
+    ts::next_tx(scenario, OWNER);
+    {
+        let mut fund = ts::take_shared<HedgeFund>(scenario);
+        apex_fund::set_entry_fee_protocol_bps(&mut fund, 2000, ts::ctx(scenario));
+        ts::return_shared(fund);
+    };
+
+    ts::next_tx(scenario, AGENT);
+    let mut fund = ts::take_shared<HedgeFund>(scenario);
+    let mut config = ts::take_shared<ProtocolConfig>(scenario);
+    let mut service = ts::take_shared<ServiceProvider>(scenario);
+    let clock = clock::create_for_testing(ts::ctx(scenario));
+    let treasury_before = apex_payments::treasury_balance(&config);
+
+    let position = apex_fund::join_fund(
+        &mut fund,
+        &mut config,
+        &mut service,
+        mint_sui(100_000_000, ts::ctx(scenario)),
+        mint_sui(10 * MIST_PER_SUI, ts::ctx(scenario)),
+        &clock,
+        ts::ctx(scenario)
+    );
+    let treasury_growth = apex_payments::treasury_balance(&config) - treasury_before;
+
+    transfer::public_transfer(position, AGENT);
+    clock::destroy_for_testing(clock);
+    ts::return_shared(service);
+    ts::return_shared(config);
+    ts::return_shared(fund);
+    treasury_growth
+}
+
+#[test]
+fun test_entry_fee_split_between_protocol_and_provider() {
+    let mut scenario = ts::begin(ADMIN);
+
+    // Entry service priced at the provider's 80% of a 0.1 SUI entry fee
+    let treasury_growth = join_split_fund(&mut scenario, 80_000_000);
+
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let fund = ts::take_shared<HedgeFund>(&scenario);
+        assert!(apex_fund::fund_entry_fee_protocol_bps(&fund) == 2000, 0);
+        assert!(apex_fund::fund_entry_fees_to_protocol(&fund) == 20_000_000, 1);
+        // The provider's 0.08 SUI less the usual 0.5% APEX fee
+        assert!(apex_fund::fund_entry_fees_to_provider(&fund) == 79_600_000, 2);
+        ts::return_shared(fund);
+    };
+    // Protocol share plus the 0.5% APEX fee on the provider's 0.08 SUI
+    assert!(treasury_growth == 20_400_000, 3);
+
+    ts::end(scenario);
+}
+
+#[test]
+fun test_entry_fee_provider_share_is_what_the_service_received() {
+    let mut scenario = ts::begin(ADMIN);
+
+    // The service only charges 0.05 SUI of the provider's 0.08 SUI share
+    let treasury_growth = join_split_fund(&mut scenario, 50_000_000);
+
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let fund = ts::take_shared<HedgeFund>(&scenario);
+        let service = ts::take_shared<ServiceProvider>(&scenario);
+        assert!(apex_fund::fund_entry_fees_to_protocol(&fund) == 20_000_000, 0);
+        // 0.05 SUI less 0.5%, not the nominal 0.08 SUI
+        assert!(apex_fund::fund_entry_fees_to_provider(&fund) == 49_750_000, 1);
+        assert!(apex_payments::service_revenue(&service) == 49_750_000, 2);
+        ts::return_shared(service);
+        ts::return_shared(fund);
+    };
+    assert!(treasury_growth == 20_250_000, 3);
+
+    // The 0.03 SUI the service didn't charge went back to the investor
+    {
+        let refund = ts::take_from_sender<Coin<SUI>>(&scenario);
+        assert!(coin::value(&refund) == 30_000_000, 4);
+        ts::return_to_sender(&scenario, refund);
+    };
+
+    ts::end(scenario);
+}
+
+/// Run a 100 SUI fund with the given hurdle to settlement after `profit` of
+/// realized gains, returning the manager fees collected
+fun settle_with_hurdle(scenario: &mut Scenario, hurdle_bps: u64, profit: u64): u64 {
+    settle_with_benchmarks(scenario, hurdle_bps, 0, profit)
+}
+
+/// settle_with_hurdle with a high-water mark (in MIST of capital) as well
+fun settle_with_benchmarks(
+    scenario: &mut Scenario,
+    hurdle_bps: u64,
+    high_water_mark: u64,
+    profit: u64
+): u64 {
+    ts::next_tx(scenario, OWNER);
+    let mut fund = apex_fund::create_fund_for_testing(
+        b"Hurdle Fund",
+        OWNER,
+        0,
+        1000 * MIST_PER_SUI,
+        ts::ctx(scenario)
+    );
+    apex_fund::set_hurdle_bps(&mut fund, hurdle_bps, ts::ctx(scenario));
+    apex_fund::set_high_water_mark(&mut fund, high_water_mark, ts::ctx(scenario));
+
+    let position = apex_fund::deposit_for_testing(
+        &mut fund,
+        mint_sui(100 * MIST_PER_SUI, ts::ctx(scenario)),
+        ts::ctx(scenario)
+    );
+    transfer::public_transfer(position, OWNER);
+
+    let clock = clock::create_for_testing(ts::ctx(scenario));
+    apex_fund::start_trading(&mut fund, &clock, ts::ctx(scenario));
+    assert!(apex_fund::fund_starting_capital(&fund) == 100 * MIST_PER_SUI, 100);
+
+    apex_fund::record_trade_profit(&mut fund, mint_sui(profit, ts::ctx(scenario)), ts::ctx(scenario));
+    apex_fund::settle_fund(&mut fund, &clock, ts::ctx(scenario));
+
+    let fees = apex_fund::withdraw_manager_fees(&mut fund, ts::ctx(scenario));
+    let collected = coin::value(&fees);
+    transfer::public_transfer(fees, OWNER);
+
+    clock::destroy_for_testing(clock);
+    apex_fund::destroy_fund_for_testing(fund);
+    collected
+}
+
+#[test]
+fun test_hurdle_blocks_performance_fee_below_benchmark() {
+    let mut scenario = ts::begin(OWNER);
+
+    // 3% return against a 5% hurdle: management fee only (2% of 103 SUI)
+    let fees = settle_with_hurdle(&mut scenario, 500, 3 * MIST_PER_SUI);
+    assert!(fees == 2_060_000_000, 0);
+
+    ts::end(scenario);
+}
+
+#[test]
+fun test_hurdle_charges_performance_fee_on_excess() {
+    let mut scenario = ts::begin(OWNER);
+
+    // 10% return against a 5% hurdle: 2% of 110 SUI + 20% of the 5 SUI excess
+    let fees = settle_with_hurdle(&mut scenario, 500, 10 * MIST_PER_SUI);
+    assert!(fees == 2_200_000_000 + 1_000_000_000, 0);
+
+    // Same return without a hurdle charges on the full 10 SUI
+    let fees = settle_with_hurdle(&mut scenario, 0, 10 * MIST_PER_SUI);
+    assert!(fees == 2_200_000_000 + 2_000_000_000, 1);
+
+    ts::end(scenario);
+}
+
+#[test]
+fun test_high_water_mark_above_hurdle_sets_the_bar() {
+    let mut scenario = ts::begin(OWNER);
+
+    // 10% return, 5% hurdle, 108 SUI high-water mark: the mark is the higher
+    // bar, so 20% applies to the 2 SUI above it (not to 10 - 5 - 8)
+    let fees = settle_with_benchmarks(&mut scenario, 500, 108 * MIST_PER_SUI, 10 * MIST_PER_SUI);
+    assert!(fees == 2_200_000_000 + 400_000_000, 0);
+
+    // Capital never gets back above a 112 SUI mark: management fee only
+    let fees = settle_with_benchmarks(&mut scenario, 0, 112 * MIST_PER_SUI, 10 * MIST_PER_SUI);
+    assert!(fees == 2_200_000_000, 1);
+
+    ts::end(scenario);
+}
+
+#[test]
+fun test_hurdle_above_high_water_mark_sets_the_bar() {
+    let mut scenario = ts::begin(OWNER);
+
+    // A 103 SUI mark is below the 105 SUI hurdle: same fees as the hurdle alone
+    let fees = settle_with_benchmarks(&mut scenario, 500, 103 * MIST_PER_SUI, 10 * MIST_PER_SUI);
+    assert!(fees == settle_with_hurdle(&mut scenario, 500, 10 * MIST_PER_SUI), 0);
+    assert!(fees == 2_200_000_000 + 1_000_000_000, 1);
+
+    // A mark below starting capital never lowers the bar
+    let fees = settle_with_benchmarks(&mut scenario, 0, 90 * MIST_PER_SUI, 10 * MIST_PER_SUI);
+    assert!(fees == 2_200_000_000 + 2_000_000_000, 2);
+
+    ts::end(scenario);
+}
+
+#[test]
+#[expected_failure(abort_code = apex_fund::ETermsLocked)]
+fun test_fund_terms_locked_once_trading() {
+    let mut scenario = ts::begin(OWNER);
+    let mut fund = apex_fund::create_fund_for_testing(
+        b"Locked Fund", OWNER, 0, 1000 * MIST_PER_SUI, ts::ctx(&mut scenario)
+    );
+    apex_fund::set_state_for_testing(&mut fund, 1);
+
+    apex_fund::set_hurdle_bps(&mut fund, 500, ts::ctx(&mut scenario));
+
+    apex_fund::destroy_fund_for_testing(fund);
+    ts::end(scenario);
+}
+
+#[test]
+#[expected_failure(abort_code = apex_fund::EUnauthorized)]
+fun test_fund_terms_manager_only() {
+    let mut scenario = ts::begin(OWNER);
+    let mut fund = apex_fund::create_fund_for_testing(
+        b"Manager Fund", OWNER, 0, 1000 * MIST_PER_SUI, ts::ctx(&mut scenario)
+    );
+
+    ts::next_tx(&mut scenario, AGENT);
+    apex_fund::set_min_trade_input(&mut fund, MIST_PER_SUI, ts::ctx(&mut scenario));
+
+    apex_fund::destroy_fund_for_testing(fund);
+    ts::end(scenario);
+}
+
+#[test]
+fun test_withdraw_after_loss_beyond_capital_pays_zero() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_settled_fund(&mut scenario, false);
+
+    // A simulated loss twice the deposit wipes out the whole pool
+    ts::next_tx(&mut scenario, OWNER);
+    {
+        let mut fund = ts::take_shared<HedgeFund>(&scenario);
+        apex_fund::simulate_loss_for_testing(&mut fund, 20 * MIST_PER_SUI);
+        assert!(apex_fund::fund_capital(&fund) == 0, 0);
+        ts::return_shared(fund);
+    };
+
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let mut fund = ts::take_shared<HedgeFund>(&scenario);
+        let position = ts::take_from_sender<InvestorPosition>(&scenario);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+
+        assert!(apex_fund::preview_withdrawal(&fund, &position) == 0, 1);
+        let (receipt, payout) = apex_fund::withdraw_shares_coin(
+            &mut fund,
+            position,
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+        assert!(coin::value(&payout) == 0, 2);
+        assert!(apex_fund::receipt_amount_received(&receipt) == 0, 3);
+        assert!(apex_fund::fund_total_shares(&fund) == 0, 4);
+
+        coin::destroy_zero(payout);
+        transfer::public_transfer(receipt, AGENT);
+        clock::destroy_for_testing(clock);
+        ts::return_shared(fund);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+fun test_withdraw_shares_coin_splits_payout() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_settled_fund(&mut scenario, false);
+
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let mut fund = ts::take_shared<HedgeFund>(&scenario);
+        let position = ts::take_from_sender<InvestorPosition>(&scenario);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+
+        let (receipt, mut payout) = apex_fund::withdraw_shares_coin(
+            &mut fund,
+            position,
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+        let total = apex_fund::receipt_amount_received(&receipt);
+        assert!(coin::value(&payout) == total, 0);
+
+        // Break the payout into three denominations, remainder stays in `payout`
+        let a = coin::split(&mut payout, 1 * MIST_PER_SUI, ts::ctx(&mut scenario));
+        let b = coin::split(&mut payout, 2 * MIST_PER_SUI, ts::ctx(&mut scenario));
+        let c = coin::split(&mut payout, 3 * MIST_PER_SUI, ts::ctx(&mut scenario));
+        assert!(coin::value(&a) == 1 * MIST_PER_SUI, 1);
+        assert!(coin::value(&b) == 2 * MIST_PER_SUI, 2);
+        assert!(coin::value(&c) == 3 * MIST_PER_SUI, 3);
+        assert!(coin::value(&payout) == total - 6 * MIST_PER_SUI, 4);
+
+        transfer::public_transfer(a, AGENT);
+        transfer::public_transfer(b, AGENT);
+        transfer::public_transfer(c, AGENT);
+        transfer::public_transfer(payout, AGENT);
+        transfer::public_transfer(receipt, AGENT);
+        clock::destroy_for_testing(clock);
+        ts::return_shared(fund);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+fun test_preview_withdrawal_matches_receipt() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_settled_fund(&mut scenario, false);
+
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let mut fund = ts::take_shared<HedgeFund>(&scenario);
+        let position = ts::take_from_sender<InvestorPosition>(&scenario);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+
+        let quoted = apex_fund::preview_withdrawal(&fund, &position);
+        let receipt = apex_fund::withdraw_shares(&mut fund, position, &clock, ts::ctx(&mut scenario));
+        assert!(quoted == apex_fund::receipt_amount_received(&receipt), 0);
+
+        transfer::public_transfer(receipt, AGENT);
+        clock::destroy_for_testing(clock);
+        ts::return_shared(fund);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+fun test_withdraw_shares_deletes_position() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_settled_fund(&mut scenario, false);
+
+    ts::next_tx(&mut scenario, AGENT);
+    let position_id = {
+        let mut fund = ts::take_shared<HedgeFund>(&scenario);
+        let position = ts::take_from_sender<InvestorPosition>(&scenario);
+        let position_id = object::id(&position);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+
+        let receipt = apex_fund::withdraw_shares(&mut fund, position, &clock, ts::ctx(&mut scenario));
+        transfer::public_transfer(receipt, AGENT);
+
+        clock::destroy_for_testing(clock);
+        ts::return_shared(fund);
+        position_id
+    };
+
+    // A redeemed position must not linger in storage
+    let effects = ts::next_tx(&mut scenario, AGENT);
+    assert!(ts::deleted(&effects).contains(&position_id), 0);
+    assert!(!ts::has_most_recent_for_sender<InvestorPosition>(&scenario), 1);
+
+    ts::end(scenario);
+}
+
+#[test]
+fun test_close_drained_fund() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_settled_fund(&mut scenario, false);
+
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let mut fund = ts::take_shared<HedgeFund>(&scenario);
+        let position = ts::take_from_sender<InvestorPosition>(&scenario);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+
+        let receipt = apex_fund::withdraw_shares(&mut fund, position, &clock, ts::ctx(&mut scenario));
+        transfer::public_transfer(receipt, AGENT);
+
+        clock::destroy_for_testing(clock);
+        ts::return_shared(fund);
+    };
+
+    ts::next_tx(&mut scenario, OWNER);
+    let fund_id = {
+        let mut fund = ts::take_shared<HedgeFund>(&scenario);
+        let fees = apex_fund::withdraw_manager_fees(&mut fund, ts::ctx(&mut scenario));
+        transfer::public_transfer(fees, OWNER);
+        assert!(apex_fund::fund_capital(&fund) == 0, 0);
+
+        let fund_id = object::id(&fund);
+        apex_fund::close_fund(fund, ts::ctx(&mut scenario));
+        fund_id
+    };
+
+    let effects = ts::next_tx(&mut scenario, OWNER);
+    assert!(ts::deleted(&effects).contains(&fund_id), 1);
+
+    ts::end(scenario);
+}
+
+#[test]
+#[expected_failure(abort_code = apex_fund::EFundNotEmpty)]
+fun test_close_fund_with_unredeemed_position() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_settled_fund(&mut scenario, false);
+
+    // Investor still holds a position backed by the pool
+    ts::next_tx(&mut scenario, OWNER);
+    {
+        let mut fund = ts::take_shared<HedgeFund>(&scenario);
+        let fees = apex_fund::withdraw_manager_fees(&mut fund, ts::ctx(&mut scenario));
+        transfer::public_transfer(fees, OWNER);
+        apex_fund::close_fund(fund, ts::ctx(&mut scenario));
+    };
+
+    ts::end(scenario);
+}
+
+/// Two settled funds in one scenario, each with a 10 SUI position held by
+/// AGENT; returns (fund A, fund B, position in A, position in B)
+fun setup_two_settled_funds(
+    scenario: &mut Scenario
+): (HedgeFund, HedgeFund, InvestorPosition, InvestorPosition) {
+    ts::next_tx(scenario, OWNER);
+    let mut fund_a = apex_fund::create_fund_for_testing(
+        b"Fund A", OWNER, 0, 1000 * MIST_PER_SUI, ts::ctx(scenario)
+    );
+    let mut fund_b = apex_fund::create_fund_for_testing(
+        b"Fund B", OWNER, 0, 1000 * MIST_PER_SUI, ts::ctx(scenario)
+    );
+
+    ts::next_tx(scenario, AGENT);
+    let position_a = apex_fund::deposit_for_testing(
+        &mut fund_a, mint_sui(10 * MIST_PER_SUI, ts::ctx(scenario)), ts::ctx(scenario)
+    );
+    let position_b = apex_fund::deposit_for_testing(
+        &mut fund_b, mint_sui(20 * MIST_PER_SUI, ts::ctx(scenario)), ts::ctx(scenario)
+    );
+
+    apex_fund::set_state_for_testing(&mut fund_a, 2);
+    apex_fund::set_state_for_testing(&mut fund_b, 2);
+    (fund_a, fund_b, position_a, position_b)
+}
+
+#[test]
+fun test_positions_redeem_only_in_their_own_fund() {
+    let mut scenario = ts::begin(ADMIN);
+    let (mut fund_a, mut fund_b, position_a, position_b) = setup_two_settled_funds(&mut scenario);
+
+    ts::next_tx(&mut scenario, AGENT);
+    let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+    let (receipt_a, payout_a) = apex_fund::withdraw_shares_coin(
+        &mut fund_a, position_a, &clock, ts::ctx(&mut scenario)
+    );
+    let (receipt_b, payout_b) = apex_fund::withdraw_shares_coin(
+        &mut fund_b, position_b, &clock, ts::ctx(&mut scenario)
+    );
+
+    // Each fund paid out exactly its own deposit and nothing else
+    assert!(coin::value(&payout_a) == 10 * MIST_PER_SUI, 0);
+    assert!(coin::value(&payout_b) == 20 * MIST_PER_SUI, 1);
+    assert!(apex_fund::fund_capital(&fund_a) == 0, 2);
+    assert!(apex_fund::fund_capital(&fund_b) == 0, 3);
+
+    transfer::public_transfer(receipt_a, AGENT);
+    transfer::public_transfer(receipt_b, AGENT);
+    transfer::public_transfer(payout_a, AGENT);
+    transfer::public_transfer(payout_b, AGENT);
+    clock::destroy_for_testing(clock);
+    apex_fund::destroy_fund_for_testing(fund_a);
+    apex_fund::destroy_fund_for_testing(fund_b);
+    ts::end(scenario);
+}
+
+#[test]
+#[expected_failure(abort_code = apex_fund::EWrongFund)]
+fun test_position_cannot_withdraw_from_other_fund() {
+    let mut scenario = ts::begin(ADMIN);
+    let (fund_a, mut fund_b, position_a, position_b) = setup_two_settled_funds(&mut scenario);
+
+    // Fund A's position against fund B's (larger) pool
+    ts::next_tx(&mut scenario, AGENT);
+    let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+    let receipt = apex_fund::withdraw_shares(&mut fund_b, position_a, &clock, ts::ctx(&mut scenario));
+
+    transfer::public_transfer(receipt, AGENT);
+    transfer::public_transfer(position_b, AGENT);
+    clock::destroy_for_testing(clock);
+    apex_fund::destroy_fund_for_testing(fund_a);
+    apex_fund::destroy_fund_for_testing(fund_b);
+    ts::end(scenario);
+}
+
+#[test]
+fun test_process_redemptions_in_batches() {
+    let mut scenario = ts::begin(OWNER);
+    let mut fund = apex_fund::create_fund_for_testing(
+        b"Batch Fund",
+        OWNER,
+        0,
+        1000 * MIST_PER_SUI,
+        ts::ctx(&mut scenario)
+    );
+
+    // 20 investors each deposit 1 SUI
+    let mut i = 0;
+    while (i < 20) {
+        let investor = sui::address::from_u256((i + 100) as u256);
+        ts::next_tx(&mut scenario, investor);
+        let position = apex_fund::deposit_for_testing(
+            &mut fund,
+            mint_sui(1 * MIST_PER_SUI, ts::ctx(&mut scenario)),
+            ts::ctx(&mut scenario)
+        );
+        transfer::public_transfer(position, investor);
+        i = i + 1;
+    };
+
+    apex_fund::set_state_for_testing(&mut fund, 2); // FUND_SETTLED
+
+    // Each investor queues their position
+    i = 0;
+    while (i < 20) {
+        let investor = sui::address::from_u256((i + 100) as u256);
+        ts::next_tx(&mut scenario, investor);
+        let position = ts::take_from_sender<InvestorPosition>(&scenario);
+        apex_fund::request_redemption(&mut fund, position, ts::ctx(&mut scenario));
+        i = i + 1;
+    };
+    assert!(apex_fund::pending_redemptions(&fund) == 20, 0);
+
+    // Four batches of 5 drain the queue
+    ts::next_tx(&mut scenario, EXECUTOR);
+    let mut batches = 0;
+    while (apex_fund::pending_redemptions(&fund) > 0) {
+        let processed = apex_fund::process_redemptions(&mut fund, 5, ts::ctx(&mut scenario));
+        assert!(processed == 5, 1);
+        batches = batches + 1;
+    };
+
+    assert!(batches == 4, 2);
+    assert!(apex_fund::redemption_cursor(&fund) == 0, 3);
+    assert!(apex_fund::fund_total_shares(&fund) == 0, 4);
+    assert!(apex_fund::fund_capital(&fund) == 0, 5);
+
+    // A drained queue is a no-op
+    assert!(apex_fund::process_redemptions(&mut fund, 5, ts::ctx(&mut scenario)) == 0, 6);
+
+    // Every investor was paid their 1 SUI back
+    ts::next_tx(&mut scenario, sui::address::from_u256(119));
+    {
+        let payout = ts::take_from_sender<Coin<SUI>>(&scenario);
+        assert!(coin::value(&payout) == 1 * MIST_PER_SUI, 7);
+        ts::return_to_sender(&scenario, payout);
+    };
+
+    apex_fund::destroy_fund_for_testing(fund);
+    ts::end(scenario);
+}
+
+#[test]
+fun test_cancel_redemption_then_withdraw() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_settled_fund(&mut scenario, false);
+
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let mut fund = ts::take_shared<HedgeFund>(&scenario);
+        let position = ts::take_from_sender<InvestorPosition>(&scenario);
+        let shares = apex_fund::position_shares(&position);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+
+        apex_fund::request_redemption(&mut fund, position, ts::ctx(&mut scenario));
+        assert!(apex_fund::pending_redemptions(&fund) == 1, 0);
+
+        // Changed my mind: the position comes back with the same shares
+        let position = apex_fund::cancel_redemption(&mut fund, 0, &clock, ts::ctx(&mut scenario));
+        assert!(apex_fund::pending_redemptions(&fund) == 0, 1);
+        assert!(apex_fund::position_shares(&position) == shares, 2);
+
+        let receipt = apex_fund::withdraw_shares(&mut fund, position, &clock, ts::ctx(&mut scenario));
+        assert!(apex_fund::receipt_shares_redeemed(&receipt) == shares, 3);
+        assert!(apex_fund::receipt_amount_received(&receipt) > 0, 4);
+
+        transfer::public_transfer(receipt, AGENT);
+        clock::destroy_for_testing(clock);
+        ts::return_shared(fund);
+    };
+
+    ts::end(scenario);
+}
+
+#[test]
+#[expected_failure(abort_code = apex_fund::ERedemptionProcessed)]
+fun test_cancel_processed_redemption() {
+    let mut scenario = ts::begin(ADMIN);
+    setup_settled_fund(&mut scenario, false);
+
+    ts::next_tx(&mut scenario, AGENT);
+    {
+        let mut fund = ts::take_shared<HedgeFund>(&scenario);
+        let position = ts::take_from_sender<InvestorPosition>(&scenario);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+
+        apex_fund::request_redemption(&mut fund, position, ts::ctx(&mut scenario));
+        apex_fund::process_redemptions(&mut fund, 1, ts::ctx(&mut scenario));
+
+        // Already paid out
+        let position = apex_fund::cancel_redemption(&mut fund, 0, &clock, ts::ctx(&mut scenario));
+
+        transfer::public_transfer(position, AGENT);
+        clock::destroy_for_testing(clock);
+        ts::return_shared(fund);
+    };
+
+    ts::end(scenario);
+}
+
+/// Fund in TRADING with AGENT (10 SUI) and RECIPIENT (20 SUI) positions
+/// queued for redemption with tickets 0 and 1
+fun setup_two_queued_redemptions(scenario: &mut Scenario): HedgeFund {
+    ts::next_tx(scenario, OWNER);
+    let mut fund = apex_fund::create_fund_for_testing(
+        b"Queue Fund", OWNER, 0, 1000 * MIST_PER_SUI, ts::ctx(scenario)
+    );
+
+    ts::next_tx(scenario, AGENT);
+    let position_a = apex_fund::deposit_for_testing(
+        &mut fund, mint_sui(10 * MIST_PER_SUI, ts::ctx(scenario)), ts::ctx(scenario)
+    );
+    ts::next_tx(scenario, RECIPIENT);
+    let position_b = apex_fund::deposit_for_testing(
+        &mut fund, mint_sui(20 * MIST_PER_SUI, ts::ctx(scenario)), ts::ctx(scenario)
+    );
+
+    // Still trading: redemptions can be queued but not yet paid
+    apex_fund::set_state_for_testing(&mut fund, 1);
+
+    ts::next_tx(scenario, AGENT);
+    apex_fund::request_redemption(&mut fund, position_a, ts::ctx(scenario));
+    ts::next_tx(scenario, RECIPIENT);
+    apex_fund::request_redemption(&mut fund, position_b, ts::ctx(scenario));
+    assert!(apex_fund::pending_redemptions(&fund) == 2, 100);
+
+    fund
+}
+
+#[test]
+fun test_cancel_redemption_keeps_later_positions() {
+    let mut scenario = ts::begin(ADMIN);
+    let mut fund = setup_two_queued_redemptions(&mut scenario);
+    let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+
+    // The first investor cancels during trading
+    ts::next_tx(&mut scenario, AGENT);
+    let position_a = apex_fund::cancel_redemption(&mut fund, 0, &clock, ts::ctx(&mut scenario));
+    assert!(apex_fund::pending_redemptions(&fund) == 1, 0);
+
+    // The second investor's ticket still finds their entry
+    ts::next_tx(&mut scenario, RECIPIENT);
+    let position_b = apex_fund::cancel_redemption(&mut fund, 1, &clock, ts::ctx(&mut scenario));
+    assert!(apex_fund::position_shares(&position_b) == 20 * MIST_PER_SUI * apex_fund::share_precision(&fund), 1);
+    assert!(apex_fund::pending_redemptions(&fund) == 0, 2);
+
+    // After settlement both withdraw normally
+    apex_fund::set_state_for_testing(&mut fund, 2);
+    ts::next_tx(&mut scenario, AGENT);
+    let receipt_a = apex_fund::withdraw_shares(&mut fund, position_a, &clock, ts::ctx(&mut scenario));
+    assert!(apex_fund::receipt_amount_received(&receipt_a) == 10 * MIST_PER_SUI, 3);
+    ts::next_tx(&mut scenario, RECIPIENT);
+    let receipt_b = apex_fund::withdraw_shares(&mut fund, position_b, &clock, ts::ctx(&mut scenario));
+    assert!(apex_fund::receipt_amount_received(&receipt_b) == 20 * MIST_PER_SUI, 4);
+
+    transfer::public_transfer(receipt_a, AGENT);
+    transfer::public_transfer(receipt_b, RECIPIENT);
+    clock::destroy_for_testing(clock);
+    apex_fund::destroy_fund_for_testing(fund);
+    ts::end(scenario);
+}
+
+#[test]
+fun test_process_redemptions_after_cancel() {
+    let mut scenario = ts::begin(ADMIN);
+    let mut fund = setup_two_queued_redemptions(&mut scenario);
+    let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+
+    ts::next_tx(&mut scenario, AGENT);
+    let position_a = apex_fund::cancel_redemption(&mut fund, 0, &clock, ts::ctx(&mut scenario));
+
+    // Only the remaining entry is paid once the fund settles
+    apex_fund::set_state_for_testing(&mut fund, 2);
+    ts::next_tx(&mut scenario, EXECUTOR);
+    assert!(apex_fund::process_redemptions(&mut fund, 5, ts::ctx(&mut scenario)) == 1, 0);
+    assert!(apex_fund::pending_redemptions(&fund) == 0, 1);
+    assert!(apex_fund::fund_capital(&fund) == 10 * MIST_PER_SUI, 2);
+
+    ts::next_tx(&mut scenario, RECIPIENT);
+    {
+        let payout = ts::take_from_sender<Coin<SUI>>(&scenario);
+        assert!(coin::value(&payout) == 20 * MIST_PER_SUI, 3);
+        ts::return_to_sender(&scenario, payout);
+    };
+
+    ts::next_tx(&mut scenario, AGENT);
+    let receipt = apex_fund::withdraw_shares(&mut fund, position_a, &clock, ts::ctx(&mut scenario));
+    assert!(apex_fund::receipt_amount_received(&receipt) == 10 * MIST_PER_SUI, 4);
+
+    transfer::public_transfer(receipt, AGENT);
+    clock::destroy_for_testing(clock);
+    apex_fund::destroy_fund_for_testing(fund);
+    ts::end(scenario);
+}
+
+#[test]
+fun test_requeue_spam_does_not_grow_queue() {
+    let mut scenario = ts::begin(ADMIN);
+    let mut fund = setup_two_queued_redemptions(&mut scenario);
+    let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+
+    // Cancelling removes the entry, so queue/cancel cycles leave nothing behind
+    ts::next_tx(&mut scenario, AGENT);
+    let mut ticket = 0;
+    let mut i = 0;
+    while (i < 10) {
+        let position = apex_fund::cancel_redemption(&mut fund, ticket, &clock, ts::ctx(&mut scenario));
+        apex_fund::request_redemption(&mut fund, position, ts::ctx(&mut scenario));
+        ticket = 2 + i;
+        i = i + 1;
+    };
+    assert!(apex_fund::pending_redemptions(&fund) == 2, 0);
+
+    // One batch of 2 pays both live requests
+    apex_fund::set_state_for_testing(&mut fund, 2);
+    ts::next_tx(&mut scenario, EXECUTOR);
+    assert!(apex_fund::process_redemptions(&mut fund, 2, ts::ctx(&mut scenario)) == 2, 1);
+    assert!(apex_fund::pending_redemptions(&fund) == 0, 2);
+    assert!(apex_fund::redemption_cursor(&fund) == 0, 3);
+
+    clock::destroy_for_testing(clock);
+    apex_fund::destroy_fund_for_testing(fund);
+    ts::end(scenario);
+}
+
+#[test]
+#[expected_failure(abort_code = apex_fund::ERedemptionProcessed)]
+fun test_cancel_redemption_twice() {
+    let mut scenario = ts::begin(ADMIN);
+    let mut fund = setup_two_queued_redemptions(&mut scenario);
+    let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+
+    ts::next_tx(&mut scenario, AGENT);
+    let first = apex_fund::cancel_redemption(&mut fund, 0, &clock, ts::ctx(&mut scenario));
+    let second = apex_fund::cancel_redemption(&mut fund, 0, &clock, ts::ctx(&mut scenario));
+
+    transfer::public_transfer(first, AGENT);
+    transfer::public_transfer(second, AGENT);
+    clock::destroy_for_testing(clock);
+    apex_fund::destroy_fund_for_testing(fund);
+    ts::end(scenario);
+}
+
+#[test]
+fun test_tiny_deposit_gets_precise_shares() {
+    let mut scenario = ts::begin(OWNER);
+    let mut fund = apex_fund::create_fund_for_testing(
+        b"Large Fund",
+        OWNER,
+        0,
+        1000 * MIST_PER_SUI,
+        ts::ctx(&mut scenario)
+    );
+
+    // Large investor seeds the fund with 100 SUI
+    ts::next_tx(&mut scenario, AGENT);
+    let whale = apex_fund::deposit_for_testing(
+        &mut fund,
+        mint_sui(100 * MIST_PER_SUI, ts::ctx(&mut scenario)),
+        ts::ctx(&mut scenario)
+    );
+    assert!(apex_fund::position_shares(&whale) == 100 * MIST_PER_SUI * apex_fund::share_precision(&fund), 0);
+    transfer::public_transfer(whale, AGENT);
+
+    // Small investor deposits the 0.1 SUI minimum
+    ts::next_tx(&mut scenario, RECIPIENT);
+    let tiny_deposit = MIST_PER_SUI / 10;
+    let minnow = apex_fund::deposit_for_testing(
+        &mut fund,
+        mint_sui(tiny_deposit, ts::ctx(&mut scenario)),
+        ts::ctx(&mut scenario)
+    );
+    let minnow_shares = apex_fund::position_shares(&minnow);
+    assert!(minnow_shares > 0, 1);
+    assert!(apex_fund::share_value(&fund, minnow_shares) <= tiny_deposit, 2);
+    assert!(apex_fund::share_value(&fund, minnow_shares) + 1 >= tiny_deposit, 3);
+
+    // After settlement the small position redeems for ~its deposit
+    apex_fund::set_state_for_testing(&mut fund, 2); // FUND_SETTLED
+    let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+    let receipt = apex_fund::withdraw_shares(&mut fund, minnow, &clock, ts::ctx(&mut scenario));
+    transfer::public_transfer(receipt, RECIPIENT);
+    clock::destroy_for_testing(clock);
+
+    ts::next_tx(&mut scenario, RECIPIENT);
+    {
+        let payout = ts::take_from_sender<Coin<SUI>>(&scenario);
+        assert!(coin::value(&payout) + 1 >= tiny_deposit, 4);
+        assert!(coin::value(&payout) <= tiny_deposit, 5);
+        ts::return_to_sender(&scenario, payout);
+    };
+
+    apex_fund::destroy_fund_for_testing(fund);
+    ts::end(scenario);
+}
+
+#[test]
+fun test_share_precision_is_per_fund() {
+    let mut scenario = ts::begin(OWNER);
+    let mut fine = apex_fund::create_fund_for_testing(
+        b"Fine Fund", OWNER, 0, 1000 * MIST_PER_SUI, ts::ctx(&mut scenario)
+    );
+    let mut coarse = apex_fund::create_fund_for_testing(
+        b"Coarse Fund", OWNER, 0, 1000 * MIST_PER_SUI, ts::ctx(&mut scenario)
+    );
+    apex_fund::set_share_precision(&mut fine, 1_000_000, ts::ctx(&mut scenario));
+    apex_fund::set_share_precision(&mut coarse, 1, ts::ctx(&mut scenario));
+
+    ts::next_tx(&mut scenario, AGENT);
+    let fine_position = apex_fund::deposit_for_testing(
+        &mut fine, mint_sui(1 * MIST_PER_SUI, ts::ctx(&mut scenario)), ts::ctx(&mut scenario)
+    );
+    let coarse_position = apex_fund::deposit_for_testing(
+        &mut coarse, mint_sui(1 * MIST_PER_SUI, ts::ctx(&mut scenario)), ts::ctx(&mut scenario)
+    );
+
+    assert!(apex_fund::position_shares(&fine_position) == MIST_PER_SUI * 1_000_000, 0);
+    assert!(apex_fund::position_shares(&coarse_position) == MIST_PER_SUI, 1);
+    // Either way the position is worth the deposit
+    assert!(apex_fund::share_value(&fine, apex_fund::position_shares(&fine_position)) == MIST_PER_SUI, 2);
+    assert!(apex_fund::share_value(&coarse, apex_fund::position_shares(&coarse_position)) == MIST_PER_SUI, 3);
+
+    transfer::public_transfer(fine_position, AGENT);
+    transfer::public_transfer(coarse_position, AGENT);
+    apex_fund::destroy_fund_for_testing(fine);
+    apex_fund::destroy_fund_for_testing(coarse);
+    ts::end(scenario);
+}
+
+#[test]
+#[expected_failure(abort_code = apex_fund::ETermsLocked)]
+fun test_share_precision_locked_after_first_deposit() {
+    let mut scenario = ts::begin(OWNER);
+    let mut fund = apex_fund::create_fund_for_testing(
+        b"Locked Fund", OWNER, 0, 1000 * MIST_PER_SUI, ts::ctx(&mut scenario)
+    );
+
+    ts::next_tx(&mut scenario, AGENT);
+    let position = apex_fund::deposit_for_testing(
+        &mut fund, mint_sui(1 * MIST_PER_SUI, ts::ctx(&mut scenario)), ts::ctx(&mut scenario)
+    );
+
+    // Rescaling now would change what existing shares are worth
+    ts::next_tx(&mut scenario, OWNER);
+    apex_fund::set_share_precision(&mut fund, 1_000_000, ts::ctx(&mut scenario));
+
+    transfer::public_transfer(position, AGENT);
+    apex_fund::destroy_fund_for_testing(fund);
+    ts::end(scenario);
+}
+
+/// Fund in the trading state with a TraderCap granted to EXECUTOR
+fun setup_fund_with_trader(scenario: &mut Scenario): (HedgeFund, ID) {
+    ts::next_tx(scenario, OWNER);
+    let mut fund = apex_fund::create_fund_for_testing(
+        b"Delegated Fund",
+        OWNER,
+        0,
+        1000 * MIST_PER_SUI,
+        ts::ctx(scenario)
+    );
+    let position = apex_fund::deposit_for_testing(
+        &mut fund,
+        mint_sui(10 * MIST_PER_SUI, ts::ctx(scenario)),
+        ts::ctx(scenario)
+    );
+    transfer::public_transfer(position, OWNER);
+
+    let clock = clock::create_for_testing(ts::ctx(scenario));
+    apex_fund::start_trading(&mut fund, &clock, ts::ctx(scenario));
+    clock::destroy_for_testing(clock);
+
+    let cap_id = apex_fund::grant_trader_cap(&mut fund, EXECUTOR, ts::ctx(scenario));
+    (fund, cap_id)
+}
+
+#[test]
+fun test_trader_cap_executes_trades() {
+    let mut scenario = ts::begin(OWNER);
+    let (mut fund, cap_id) = setup_fund_with_trader(&mut scenario);
+    assert!(apex_fund::is_trader_cap_active(&fund, cap_id), 0);
+
+    ts::next_tx(&mut scenario, EXECUTOR);
+    {
+        let cap = ts::take_from_sender<TraderCap>(&scenario);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+
+        let record = apex_fund::execute_trader_trade(
+            &cap,
+            &mut fund,
+            b"SUI/USDC",
+            1 * MIST_PER_SUI,
+            1_100_000_000,
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+        transfer::public_transfer(record, EXECUTOR);
+
+        let (pnl, is_profit) = apex_fund::fund_realized_pnl(&fund);
+        assert!(pnl == 100_000_000 && is_profit, 1);
+
+        clock::destroy_for_testing(clock);
+        ts::return_to_sender(&scenario, cap);
+    };
+
+    apex_fund::destroy_fund_for_testing(fund);
+    ts::end(scenario);
+}
+
+#[test]
+fun test_fund_keeps_trade_ids_in_order() {
+    let mut scenario = ts::begin(OWNER);
+    let (mut fund, _cap_id) = setup_fund_with_trader(&mut scenario);
+    assert!(apex_fund::trade_count(&fund) == 0, 0);
+
+    // Manager and delegated trader both land in the same log
+    ts::next_tx(&mut scenario, OWNER);
+    let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+    let first = apex_fund::execute_margin_trade(
+        &mut fund, b"MARGIN_LONG", 1 * MIST_PER_SUI, 1_100_000_000, &clock, ts::ctx(&mut scenario)
+    );
+    let second = apex_fund::execute_margin_trade(
+        &mut fund, b"MARGIN_SHORT", 1 * MIST_PER_SUI, 900_000_000, &clock, ts::ctx(&mut scenario)
+    );
+    let ids = vector[object::id(&first), object::id(&second)];
+    transfer::public_transfer(first, OWNER);
+    transfer::public_transfer(second, OWNER);
+    clock::destroy_for_testing(clock);
+
+    ts::next_tx(&mut scenario, EXECUTOR);
+    {
+        let cap = ts::take_from_sender<TraderCap>(&scenario);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        let third = apex_fund::execute_trader_trade(
+            &cap, &mut fund, b"SPOT", 1 * MIST_PER_SUI, 1_000_000_000, &clock, ts::ctx(&mut scenario)
+        );
+
+        assert!(apex_fund::trade_count(&fund) == 3, 1);
+        assert!(apex_fund::trade_id_at(&fund, 0) == ids[0], 2);
+        assert!(apex_fund::trade_id_at(&fund, 1) == ids[1], 3);
+        assert!(apex_fund::trade_id_at(&fund, 2) == object::id(&third), 4);
+
+        transfer::public_transfer(third, EXECUTOR);
+        clock::destroy_for_testing(clock);
+        ts::return_to_sender(&scenario, cap);
+    };
+
+    apex_fund::destroy_fund_for_testing(fund);
+    ts::end(scenario);
+}
+
+#[test]
+#[expected_failure(abort_code = apex_fund::EZeroAddress)]
+fun test_grant_trader_cap_to_zero_address() {
+    let mut scenario = ts::begin(OWNER);
+    let (mut fund, _cap_id) = setup_fund_with_trader(&mut scenario);
+
+    ts::next_tx(&mut scenario, OWNER);
+    apex_fund::grant_trader_cap(&mut fund, @0x0, ts::ctx(&mut scenario));
+
+    apex_fund::destroy_fund_for_testing(fund);
+    ts::end(scenario);
+}
+
+#[test]
+fun test_trade_at_minimum_size() {
+    let mut scenario = ts::begin(OWNER);
+    let (mut fund, _cap_id) = setup_fund_with_trader(&mut scenario);
+    apex_fund::set_min_trade_input_for_testing(&mut fund, 100_000_000);
+    assert!(apex_fund::fund_min_trade_input(&fund) == 100_000_000, 0);
+
+    ts::next_tx(&mut scenario, OWNER);
+    let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+    let record = apex_fund::execute_margin_trade(
+        &mut fund, b"MARGIN_LONG", 100_000_000, 110_000_000, &clock, ts::ctx(&mut scenario)
+    );
+    assert!(apex_fund::trade_count(&fund) == 1, 1);
+
+    transfer::public_transfer(record, OWNER);
+    clock::destroy_for_testing(clock);
+    apex_fund::destroy_fund_for_testing(fund);
+    ts::end(scenario);
+}
+
+#[test]
+#[expected_failure(abort_code = apex_fund::EBelowMinTrade)]
+fun test_trade_below_minimum_size() {
+    let mut scenario = ts::begin(OWNER);
+    let (mut fund, _cap_id) = setup_fund_with_trader(&mut scenario);
+    apex_fund::set_min_trade_input_for_testing(&mut fund, 100_000_000);
+
+    // One MIST short of the minimum
+    ts::next_tx(&mut scenario, OWNER);
+    let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+    let record = apex_fund::execute_margin_trade(
+        &mut fund, b"MARGIN_LONG", 99_999_999, 110_000_000, &clock, ts::ctx(&mut scenario)
+    );
+
+    transfer::public_transfer(record, OWNER);
+    clock::destroy_for_testing(clock);
+    apex_fund::destroy_fund_for_testing(fund);
+    ts::end(scenario);
+}
+
+#[test]
+#[expected_failure(abort_code = apex_fund::EUnauthorized)]
+fun test_trader_cap_cannot_settle() {
+    let mut scenario = ts::begin(OWNER);
+    let (mut fund, _cap_id) = setup_fund_with_trader(&mut scenario);
+
+    // Trading rights do not extend to fund administration
+    ts::next_tx(&mut scenario, EXECUTOR);
+    {
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+        apex_fund::settle_fund(&mut fund, &clock, ts::ctx(&mut scenario));
+        clock::destroy_for_testing(clock);
+    };
+
+    apex_fund::destroy_fund_for_testing(fund);
+    ts::end(scenario);
+}
+
+#[test]
+fun test_burn_trader_cap_after_fund_is_gone() {
+    let mut scenario = ts::begin(OWNER);
+    let (fund, _cap_id) = setup_fund_with_trader(&mut scenario);
+    apex_fund::destroy_fund_for_testing(fund);
+
+    // No fund left to hand to destroy_trader_cap
+    ts::next_tx(&mut scenario, EXECUTOR);
+    {
+        let cap = ts::take_from_sender<TraderCap>(&scenario);
+        apex_fund::burn_trader_cap(cap);
+    };
+
+    ts::next_tx(&mut scenario, EXECUTOR);
+    assert!(!ts::has_most_recent_for_sender<TraderCap>(&scenario), 0);
+
+    ts::end(scenario);
+}
+
+#[test]
+#[expected_failure(abort_code = apex_fund::ETraderCapRevoked)]
+fun test_revoked_trader_cap_cannot_trade() {
+    let mut scenario = ts::begin(OWNER);
+    let (mut fund, cap_id) = setup_fund_with_trader(&mut scenario);
+
+    ts::next_tx(&mut scenario, OWNER);
+    apex_fund::revoke_trader_cap(&mut fund, cap_id, ts::ctx(&mut scenario));
+    assert!(!apex_fund::is_trader_cap_active(&fund, cap_id), 0);
+
+    ts::next_tx(&mut scenario, EXECUTOR);
+    {
+        let cap = ts::take_from_sender<TraderCap>(&scenario);
+        let clock = clock::create_for_testing(ts::ctx(&mut scenario));
+
+        let record = apex_fund::execute_trader_trade(
+            &cap,
+            &mut fund,
+            b"SUI/USDC",
+            1 * MIST_PER_SUI,
+            1_100_000_000,
+            &clock,
+            ts::ctx(&mut scenario)
+        );
+        transfer::public_transfer(record, EXECUTOR);
+
+        clock::destroy_for_testing(clock);
+        ts::return_to_sender(&scenario, cap);
     };
 
+    apex_fund::destroy_fund_for_testing(fund);
     ts::end(scenario);
 }
@@ -145,14 +145,15 @@ public fun open_verified_access_session(
     // Verify meter is trusted and active
     assert!(apex_payments::meter_is_active(meter), EMeterNotTrusted);
 
-    // Purchase access capability
-    let capability = apex_payments::purchase_access(
+    // Purchase access capability (no rate limit for verified sessions);
+    // the package-internal path also admits meter-required services
+    let capability = apex_payments::purchase_metered_access(
         config,
         service,
+        meter,
         payment,
         units,
         duration_ms,
-        0, // no rate limit for verified sessions
         clock,
         ctx
     );
@@ -405,6 +406,10 @@ public fun create_stream_checkpoint(
 
 /// Atomic verification for Seal access - combines capability + meter verification
 /// Used by Seal key servers to verify access before releasing keys
+///
+/// Must stay read-only: key servers run it via dry_run_transaction_block, so it
+/// takes every object by immutable reference and never creates objects or emits
+/// events. Units are consumed later through close_verified_access_session.
 entry fun verify_seal_access_atomic(
     capability: &AccessCapability,
     service: &ServiceProvider,
@@ -548,6 +553,29 @@ public fun burn_checkpoint(checkpoint: StreamCheckpoint) {
 
 // ==================== Test Helpers ====================
 
+#[test_only]
+public fun verify_seal_access_atomic_for_testing(
+    capability: &AccessCapability,
+    service: &ServiceProvider,
+    meter: &TrustedMeter,
+    content_id: vector<u8>,
+    min_units: u64,
+    recent_verification_signature: vector<u8>,
+    recent_verification_timestamp: u64,
+    clock: &Clock,
+) {
+    verify_seal_access_atomic(
+        capability,
+        service,
+        meter,
+        content_id,
+        min_units,
+        recent_verification_signature,
+        recent_verification_timestamp,
+        clock
+    )
+}
+
 #[test_only]
 public fun create_verified_result_for_testing(
     service_id: ID,